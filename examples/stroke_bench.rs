@@ -0,0 +1,33 @@
+//! Time `Curve::push` while drawing a long stroke
+//!
+//! `cargo run --release --example stroke_bench [samples]`
+use std::time::Instant;
+
+use iced::{Color, Point};
+use monstera::bench::Bench;
+use monstera::curve::{Curve, Sample};
+
+fn main() {
+    let samples: usize = std::env::args()
+        .nth(1)
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(10_000);
+
+    let mut bench = Bench::new();
+    let mut curve = Curve::new(vec![], Color::WHITE);
+
+    let start = Instant::now();
+    for i in 0..samples {
+        let t = i as f32 * 0.05;
+        let point = Point::new(t * 10. + t.sin() * 30., t.cos() * 30.);
+
+        let update_start = Instant::now();
+        curve.push(Sample::simulated(curve.last(), point));
+        let update_stop = Instant::now();
+
+        bench.add_update(update_start, update_stop);
+        bench.add_total(update_start, update_stop);
+    }
+    println!("{samples} samples in {:?}", start.elapsed());
+    println!("{}", bench.summary());
+}
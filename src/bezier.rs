@@ -0,0 +1,338 @@
+//! Fitting piecewise cubic béziers to ink, after Philip J. Schneider's
+//! "An Algorithm for Automatically Fitting Digitized Curves" (Graphics Gems, 1990)
+use glam::Vec2;
+use iced::Point;
+
+use crate::curve::Sample;
+
+/// Newton-Raphson reparameterization is only attempted when the error is within this
+/// factor of the target, further away it's cheaper to split
+const REPARAMETERIZE_FACTOR: f32 = 4.0;
+const MAX_ITERATIONS: usize = 4;
+
+/// A cubic bézier segment, the end points carry the pen pressure at that point
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CubicBezier {
+    pub from: Sample,
+    pub control_a: Point,
+    pub control_b: Point,
+    pub to: Sample,
+}
+
+impl CubicBezier {
+    pub fn point(&self, t: f32) -> Point {
+        let [p0, p1, p2, p3] = self.points();
+        let mt = 1.0 - t;
+        let p = p0 * (mt * mt * mt)
+            + p1 * (3.0 * mt * mt * t)
+            + p2 * (3.0 * mt * t * t)
+            + p3 * (t * t * t);
+        Point::new(p.x, p.y)
+    }
+
+    /// Pen pressure at `t`, interpolated between the end points
+    pub fn pressure(&self, t: f32) -> f32 {
+        self.from.pressure + (self.to.pressure - self.from.pressure) * t
+    }
+
+    /// Approximate the segment with samples roughly `step` apart, excluding the start
+    pub fn flatten(&self, step: f32) -> impl Iterator<Item = Sample> + '_ {
+        let [p0, p1, p2, p3] = self.points();
+        // the control polygon is never shorter than the curve
+        let length = p0.distance(p1) + p1.distance(p2) + p2.distance(p3);
+        let steps = ((length / step).ceil() as usize).max(1);
+        (1..=steps).map(move |i| {
+            let t = i as f32 / steps as f32;
+            self.from.lerp(&self.to, self.point(t), t)
+        })
+    }
+
+    fn points(&self) -> [Vec2; 4] {
+        [
+            vec2(self.from.point),
+            vec2(self.control_a),
+            vec2(self.control_b),
+            vec2(self.to.point),
+        ]
+    }
+}
+
+/// Fit béziers through `samples` so no sample is further than `max_error` from the fit.
+/// `pressure_scale` is how far the edge of the stroke moves per unit of pressure, so
+/// changes in width count towards the error too. Each segment starts where the
+/// previous one ends.
+pub fn fit(samples: &[Sample], max_error: f32, pressure_scale: f32) -> Vec<CubicBezier> {
+    let mut samples = samples.to_vec();
+    samples.dedup_by(|a, b| a.point == b.point);
+    if samples.len() < 2 {
+        return vec![];
+    }
+    let points: Vec<Vec2> = samples.iter().map(|s| vec2(s.point)).collect();
+    let last = points.len() - 1;
+
+    let mut segments = vec![];
+    // Worked depth first without recursion, the right half is pushed first so
+    // segments come out in order
+    let mut spans = vec![(
+        0,
+        last,
+        (points[1] - points[0]).normalize(),
+        (points[last - 1] - points[last]).normalize(),
+    )];
+    while let Some((first, last, tangent_start, tangent_end)) = spans.pop() {
+        let span = &points[first..=last];
+        let span_samples = &samples[first..=last];
+        let error_of = |params: &[f32], control: &[Vec2; 4]| {
+            worst_error(span, span_samples, params, control, pressure_scale)
+        };
+
+        let mut params = chord_length_parameterize(span);
+        let mut control = generate_bezier(span, &params, tangent_start, tangent_end);
+        let (mut error, mut split) = error_of(&params, &control);
+        if error > max_error && error < max_error * REPARAMETERIZE_FACTOR {
+            for _ in 0..MAX_ITERATIONS {
+                params = reparameterize(span, &params, &control);
+                control = generate_bezier(span, &params, tangent_start, tangent_end);
+                (error, split) = error_of(&params, &control);
+                if error <= max_error {
+                    break;
+                }
+            }
+        }
+
+        if error <= max_error {
+            segments.push(CubicBezier {
+                from: samples[first],
+                control_a: point(control[1]),
+                control_b: point(control[2]),
+                to: samples[last],
+            });
+        } else {
+            let split = first + split;
+            let center = center_tangent(&points, split);
+            spans.push((split, last, -center, tangent_end));
+            spans.push((first, split, tangent_start, center));
+        }
+    }
+    segments
+}
+
+/// Least squares fit of the control point distances along the end tangents
+fn generate_bezier(
+    span: &[Vec2],
+    params: &[f32],
+    tangent_start: Vec2,
+    tangent_end: Vec2,
+) -> [Vec2; 4] {
+    let first = span[0];
+    let last = span[span.len() - 1];
+
+    let mut c = [[0.0f32; 2]; 2];
+    let mut x = [0.0f32; 2];
+    for (&p, &t) in span.iter().zip(params) {
+        let mt = 1.0 - t;
+        let a1 = tangent_start * (3.0 * mt * mt * t);
+        let a2 = tangent_end * (3.0 * mt * t * t);
+        c[0][0] += a1.dot(a1);
+        c[0][1] += a1.dot(a2);
+        c[1][1] += a2.dot(a2);
+
+        let shortfall = p
+            - (first * (mt * mt * mt + 3.0 * mt * mt * t) + last * (3.0 * mt * t * t + t * t * t));
+        x[0] += a1.dot(shortfall);
+        x[1] += a2.dot(shortfall);
+    }
+    c[1][0] = c[0][1];
+
+    let det_c = c[0][0] * c[1][1] - c[1][0] * c[0][1];
+    let (alpha_start, alpha_end) = if det_c.abs() > f32::EPSILON {
+        (
+            (x[0] * c[1][1] - x[1] * c[0][1]) / det_c,
+            (c[0][0] * x[1] - c[1][0] * x[0]) / det_c,
+        )
+    } else {
+        (0.0, 0.0)
+    };
+
+    // Fall back to the Wu/Barsky heuristic when the solution is degenerate
+    let chord = first.distance(last);
+    let epsilon = 1.0e-6 * chord;
+    let (alpha_start, alpha_end) = if alpha_start < epsilon || alpha_end < epsilon {
+        (chord / 3.0, chord / 3.0)
+    } else {
+        (alpha_start, alpha_end)
+    };
+
+    [
+        first,
+        first + tangent_start * alpha_start,
+        last + tangent_end * alpha_end,
+        last,
+    ]
+}
+
+/// Largest distance from the fit, either in position or in the edge of the stroke
+/// when the pressure strays from the interpolated pressure
+fn worst_error(
+    span: &[Vec2],
+    samples: &[Sample],
+    params: &[f32],
+    control: &[Vec2; 4],
+    pressure_scale: f32,
+) -> (f32, usize) {
+    let (pressure_start, pressure_end) = (samples[0].pressure, samples[samples.len() - 1].pressure);
+    let mut worst = (0.0, span.len() / 2);
+    for i in 1..span.len() - 1 {
+        let t = params[i];
+        let distance = evaluate(control, t).distance(span[i]);
+        let pressure = pressure_start + (pressure_end - pressure_start) * t;
+        let error = distance.max((samples[i].pressure - pressure).abs() * pressure_scale);
+        if error >= worst.0 {
+            worst = (error, i);
+        }
+    }
+    worst
+}
+
+fn chord_length_parameterize(span: &[Vec2]) -> Vec<f32> {
+    let mut params = Vec::with_capacity(span.len());
+    let mut total = 0.0;
+    params.push(0.0);
+    for pair in span.windows(2) {
+        total += pair[0].distance(pair[1]);
+        params.push(total);
+    }
+    if total > 0.0 {
+        params.iter_mut().for_each(|t| *t /= total);
+    }
+    params
+}
+
+/// Move each parameter towards the closest point on the curve with a Newton-Raphson step
+fn reparameterize(span: &[Vec2], params: &[f32], control: &[Vec2; 4]) -> Vec<f32> {
+    let derivative = [
+        (control[1] - control[0]) * 3.0,
+        (control[2] - control[1]) * 3.0,
+        (control[3] - control[2]) * 3.0,
+    ];
+    let second = [
+        (derivative[1] - derivative[0]) * 2.0,
+        (derivative[2] - derivative[1]) * 2.0,
+    ];
+    span.iter()
+        .zip(params)
+        .map(|(&p, &t)| {
+            let offset = evaluate(control, t) - p;
+            let d1 = evaluate(&derivative, t);
+            let d2 = evaluate(&second, t);
+            let numerator = offset.dot(d1);
+            let denominator = d1.dot(d1) + offset.dot(d2);
+            if denominator.abs() < f32::EPSILON {
+                t
+            } else {
+                (t - numerator / denominator).clamp(0.0, 1.0)
+            }
+        })
+        .collect()
+}
+
+fn center_tangent(points: &[Vec2], center: usize) -> Vec2 {
+    let tangent = (points[center - 1] - points[center + 1]).normalize_or_zero();
+    if tangent == Vec2::ZERO {
+        (points[center - 1] - points[center]).normalize()
+    } else {
+        tangent
+    }
+}
+
+/// Evaluate a bézier of any degree with de Casteljau's algorithm
+fn evaluate<const N: usize>(control: &[Vec2; N], t: f32) -> Vec2 {
+    let mut points = *control;
+    for degree in (1..N).rev() {
+        for i in 0..degree {
+            points[i] = points[i].lerp(points[i + 1], t);
+        }
+    }
+    points[0]
+}
+
+fn vec2(point: Point) -> Vec2 {
+    Vec2::new(point.x, point.y)
+}
+
+fn point(vec: Vec2) -> Point {
+    Point::new(vec.x, vec.y)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Samples along a sine wave, one unit apart in x
+    fn wave(count: usize) -> Vec<Sample> {
+        (0..count)
+            .map(|i| {
+                let x = i as f32;
+                Sample::new(Point::new(x, (x / 8.).sin() * 20.), 0.5)
+            })
+            .collect()
+    }
+
+    /// Distance from `point` to the segments, flattened finely
+    fn distance(segments: &[CubicBezier], point: Point) -> f32 {
+        segments
+            .iter()
+            .flat_map(|segment| std::iter::once(segment.from).chain(segment.flatten(0.05)))
+            .map(|sample| sample.point.distance(point))
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    #[test]
+    fn fit_stays_within_max_error() {
+        let samples = wave(200);
+        let max_error = 0.5;
+        let segments = fit(&samples, max_error, 0.);
+        assert!(!segments.is_empty() && segments.len() < samples.len() / 4);
+        for sample in &samples {
+            // flattening adds a little error of its own
+            assert!(distance(&segments, sample.point) <= max_error + 0.05);
+        }
+    }
+
+    #[test]
+    fn fit_is_continuous_from_end_to_end() {
+        let samples = wave(200);
+        let segments = fit(&samples, 0.5, 0.);
+        assert_eq!(segments[0].from, samples[0]);
+        assert_eq!(segments[segments.len() - 1].to, samples[samples.len() - 1]);
+        for pair in segments.windows(2) {
+            assert_eq!(pair[0].to, pair[1].from);
+        }
+    }
+
+    #[test]
+    fn fit_straight_line_with_one_segment() {
+        let samples: Vec<Sample> = (0..50)
+            .map(|i| Sample::new(Point::new(i as f32, 0.), 0.5))
+            .collect();
+        assert_eq!(fit(&samples, 0.1, 0.).len(), 1);
+    }
+
+    #[test]
+    fn fit_splits_on_changes_in_width() {
+        // a straight line that swells in the middle
+        let samples: Vec<Sample> = (0..=20)
+            .map(|i| {
+                let pressure = 1. - (i as f32 - 10.).abs() / 10.;
+                Sample::new(Point::new(i as f32, 0.), pressure)
+            })
+            .collect();
+        assert!(fit(&samples, 0.5, 4.).len() > 1);
+    }
+
+    #[test]
+    fn fit_nothing_to_a_dot() {
+        let dot = Sample::new(Point::new(3., 4.), 0.5);
+        assert!(fit(&[dot, dot, dot], 0.5, 0.).is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+//! Exports from the command line, without a window or a GPU
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use iced::Rectangle;
+use monstera::document::Document;
+use monstera::export::pdf::{self, Paper};
+use monstera::export::{png, svg};
+
+pub const USAGE: &str = "\
+usage: monstera
+       monstera --export <document> <output.png|output.svg|output.pdf> [options]
+
+options:
+    --scale <factor>             pixels per world unit of a PNG, 1 by default
+    --region <x,y,width,height>  export only this area, in world coordinates
+    --transparent                leave out the canvas background of a PNG or PDF
+    --paper <a4|letter>          split a PDF into pages of this size, instead of
+                                 a single page fitted to the drawing";
+
+struct Export {
+    document: PathBuf,
+    output: PathBuf,
+    scale: f32,
+    region: Option<Rectangle>,
+    transparent: bool,
+    layout: pdf::Layout,
+}
+
+/// Run what the arguments ask for, `None` if they don't ask for anything and the
+/// window should open as usual
+pub fn run(args: impl IntoIterator<Item = String>) -> Option<Result<(), String>> {
+    let mut args = args.into_iter().peekable();
+    args.peek()?;
+    Some(parse(args).and_then(|export| export.run()))
+}
+
+fn parse(mut args: impl Iterator<Item = String>) -> Result<Export, String> {
+    let (Some(flag), Some(document), Some(output)) = (args.next(), args.next(), args.next()) else {
+        return Err(USAGE.into());
+    };
+    if flag != "--export" {
+        return Err(USAGE.into());
+    }
+    let mut export = Export {
+        document: document.into(),
+        output: output.into(),
+        scale: 1.,
+        region: None,
+        transparent: false,
+        layout: pdf::Layout::Fit,
+    };
+    while let Some(option) = args.next() {
+        match option.as_str() {
+            "--scale" => {
+                export.scale = args
+                    .next()
+                    .and_then(|scale| scale.parse().ok())
+                    .filter(|scale: &f32| *scale > 0.)
+                    .ok_or("--scale needs a positive number")?;
+            }
+            "--region" => {
+                let region = args.next().unwrap_or_default();
+                let numbers: Vec<f32> = region
+                    .split(',')
+                    .map(|n| n.trim().parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| "--region needs x,y,width,height")?;
+                let &[x, y, width, height] = numbers.as_slice() else {
+                    return Err("--region needs x,y,width,height".into());
+                };
+                export.region = Some(Rectangle {
+                    x,
+                    y,
+                    width,
+                    height,
+                });
+            }
+            "--transparent" => export.transparent = true,
+            "--paper" => {
+                let paper = match args.next().as_deref() {
+                    Some("a4" | "A4") => Paper::A4,
+                    Some("letter" | "Letter") => Paper::Letter,
+                    _ => return Err("--paper needs a4 or letter".into()),
+                };
+                export.layout = pdf::Layout::Pages(paper);
+            }
+            option => return Err(format!("unknown option {option}\n\n{USAGE}")),
+        }
+    }
+    Ok(export)
+}
+
+impl Export {
+    fn run(&self) -> Result<(), String> {
+        let document = Document::load(&self.document)
+            .map_err(|error| format!("could not open {}: {error}", self.document.display()))?;
+        let curves = document.layers().composed(&document.curves());
+
+        let contents = match extension(&self.output) {
+            Some(png::EXTENSION) => {
+                png::export(&curves, self.region, self.scale, !self.transparent)
+                    .map_err(|error| error.to_string())?
+            }
+            Some(svg::EXTENSION) => svg::export(&curves, self.region)
+                .ok_or("nothing to export")?
+                .into_bytes(),
+            Some(pdf::EXTENSION) => {
+                pdf::export(&curves, self.region, self.layout, !self.transparent)
+                    .ok_or("nothing to export")?
+            }
+            _ => return Err(format!("can only export to .png, .svg or .pdf\n\n{USAGE}")),
+        };
+        fs::write(&self.output, contents)
+            .map_err(|error| format!("could not write {}: {error}", self.output.display()))
+    }
+}
+
+fn extension(path: &Path) -> Option<&str> {
+    path.extension().and_then(|extension| extension.to_str())
+}
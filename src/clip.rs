@@ -0,0 +1,179 @@
+//! Copying and pasting curves through the system clipboard
+//!
+//! Curves are copied as an SVG document, so other apps can paste them as a drawing,
+//! that carries the curves themselves in its metadata:
+//!
+//! ```xml
+//! <svg xmlns="http://www.w3.org/2000/svg" viewBox="..." ...>
+//!   <metadata id="monstera-clip"><![CDATA[{ "format": "monstera-clip", "version": 4, "curves": [...] }]]></metadata>
+//!   ...
+//! </svg>
+//! ```
+//!
+//! `curves` are stored as in a document of format `version`, see [`crate::document`],
+//! so pasting gives back exactly the curves that were copied. Text without them is
+//! imported like an SVG file.
+use std::fmt;
+
+use iced::{Color, Point};
+use serde::Serialize;
+
+use crate::curve::Curve;
+use crate::document::{self, CurveData, DocumentError, FORMAT_VERSION};
+use crate::export::{self, svg};
+use crate::import::{self, ImportError};
+use crate::transform::Transform;
+
+/// `format` of the copied curves, and `id` of the metadata holding them
+pub const FORMAT: &str = "monstera-clip";
+
+#[derive(Debug)]
+pub enum PasteError {
+    /// Not copied curves, and not an SVG that can be imported either
+    Import(ImportError),
+    /// The copied curves are damaged
+    Parse(serde_json::Error),
+    /// Copied by a monstera whose curves this one can't read
+    Version(DocumentError),
+}
+
+impl fmt::Display for PasteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PasteError::Import(error) => write!(f, "{error}"),
+            PasteError::Parse(error) => write!(f, "the copied curves are damaged: {error}"),
+            PasteError::Version(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for PasteError {}
+
+impl From<ImportError> for PasteError {
+    fn from(error: ImportError) -> Self {
+        PasteError::Import(error)
+    }
+}
+
+impl From<roxmltree::Error> for PasteError {
+    fn from(error: roxmltree::Error) -> Self {
+        PasteError::Import(ImportError::Xml(error))
+    }
+}
+
+impl From<serde_json::Error> for PasteError {
+    fn from(error: serde_json::Error) -> Self {
+        PasteError::Parse(error)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Clip {
+    format: String,
+    version: u32,
+    curves: Vec<CurveData>,
+}
+
+/// Text to put on the clipboard for `curves`, `None` if there are none
+pub fn copy(curves: &[Curve]) -> Option<String> {
+    let clip = Clip {
+        format: FORMAT.into(),
+        version: FORMAT_VERSION,
+        curves: curves.iter().map(CurveData::from).collect(),
+    };
+    let json = serde_json::to_string(&clip).ok()?;
+    svg::export_with_metadata(
+        curves,
+        &format!(r#"<metadata id="{FORMAT}"><![CDATA[{json}]]></metadata>"#),
+    )
+}
+
+/// Curves of text from the clipboard, centered on `at`. Imported elements that set
+/// neither a stroke nor a fill are drawn in `fallback`.
+pub fn paste(text: &str, at: Point, fallback: Color) -> Result<Vec<Curve>, PasteError> {
+    let document = roxmltree::Document::parse(text)?;
+    let clip = document
+        .descendants()
+        .find(|node| node.has_tag_name("metadata") && node.attribute("id") == Some(FORMAT))
+        .and_then(|node| node.text());
+    let curves = match clip {
+        Some(json) => {
+            // copied by an older monstera, the curves are migrated like a document's
+            let mut clip: serde_json::Value = serde_json::from_str(json)?;
+            let version = clip["version"].as_u64().unwrap_or(0) as u32;
+            document::check_version(version).map_err(PasteError::Version)?;
+            document::migrate_curves(&mut clip["curves"], version);
+            let curves: Vec<CurveData> = serde_json::from_value(clip["curves"].take())?;
+            curves.iter().map(Curve::from).collect()
+        }
+        None => import::import(text, at, fallback)?,
+    };
+
+    let bounds = export::bounds(&curves).ok_or(ImportError::Empty)?;
+    let moved = Transform {
+        offset: at - bounds.center(),
+        ..Transform::default()
+    };
+    Ok(curves
+        .iter()
+        .map(|curve| curve.transformed(&moved))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::Sample;
+
+    fn curve(x: f32) -> Curve {
+        let samples = vec![
+            Sample::new(Point::new(x, 0.), 0.3),
+            Sample::new(Point::new(x + 10., 5.), 0.8),
+            Sample::new(Point::new(x + 20., 0.), 0.5),
+        ];
+        Curve::with_width(samples, Color::from_rgba(0.2, 0.4, 0.6, 0.5), 7.)
+    }
+
+    #[test]
+    fn paste_gives_back_what_was_copied() {
+        let copied = [curve(0.), curve(100.)];
+        let text = copy(&copied).expect("a copy");
+        let at = Point::new(500., 500.);
+        let pasted = paste(&text, at, Color::WHITE).expect("a paste");
+
+        assert_eq!(pasted.len(), copied.len());
+        for (pasted, copied) in pasted.iter().zip(&copied) {
+            assert_eq!(pasted.color(), copied.color());
+            assert_eq!(pasted.width(), copied.width());
+            let pressures = |curve: &Curve| -> Vec<f32> {
+                curve.samples().iter().map(|s| s.pressure).collect()
+            };
+            assert_eq!(pressures(pasted), pressures(copied));
+        }
+        let bounds = export::bounds(&pasted).expect("bounds");
+        assert!(bounds.center().distance(at) < 0.01);
+    }
+
+    #[test]
+    fn copy_nothing() {
+        assert!(copy(&[]).is_none());
+    }
+
+    #[test]
+    fn paste_other_svg_imports_it() {
+        let text = r##"<svg xmlns="http://www.w3.org/2000/svg">
+            <line x1="0" y1="0" x2="40" y2="0" stroke="#ff0000" stroke-width="3"/></svg>"##;
+        let pasted = paste(text, Point::ORIGIN, Color::WHITE).expect("a paste");
+        assert_eq!(pasted.len(), 1);
+        assert_eq!(pasted[0].color(), Color::from_rgb8(255, 0, 0));
+        assert_eq!(pasted[0].width(), 3.);
+    }
+
+    #[test]
+    fn paste_text_that_isnt_svg() {
+        assert!(matches!(
+            paste("just words", Point::ORIGIN, Color::WHITE),
+            Err(PasteError::Import(ImportError::Xml(_)))
+        ));
+    }
+}
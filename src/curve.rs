@@ -1,17 +1,119 @@
+use std::ops::Range;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::bezier::{self, CubicBezier};
+use crate::geometry::{self as geo, BoundsIndex};
+use crate::layer::LayerId;
+use crate::shape::Shape;
+use crate::transform::Transform;
 use iced::{
     advanced::graphics::geometry,
-    widget::canvas::{Frame, Path, Stroke},
-    Color, Point,
+    widget::canvas::{path::Builder, Frame, LineCap, LineJoin, Path, Stroke},
+    Color, Point, Rectangle, Vector,
 };
 
-type RawCurve = Vec<Point>;
+/// Fraction of the full width drawn at zero pressure, so light strokes don't vanish
+const MIN_WIDTH_RATIO: f32 = 0.2;
+/// Width of the stroke at full pressure
+pub const DEFAULT_WIDTH: f32 = 2.0;
+/// Pressure assumed for input devices that don't report any
+pub const DEFAULT_PRESSURE: f32 = 0.5;
+/// Cubic bezier handle length for approximating a quarter circle
+const KAPPA: f32 = 0.552_284_8;
+/// Spacing of the samples kept for a fitted curve, in world units
+const FLATTEN_STEP: f32 = 1.0;
+
+/// A single input sample of a stroke
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub point: Point,
+    /// Normalized pen pressure in `0.0..=1.0`
+    pub pressure: f32,
+    /// When the sample was drawn, in seconds since the Unix epoch, 0 if unknown
+    pub time: f64,
+}
+
+impl Sample {
+    pub fn new(point: Point, pressure: f32) -> Self {
+        Sample {
+            point,
+            pressure: pressure.clamp(0.0, 1.0),
+            time: 0.,
+        }
+    }
+
+    pub fn at(self, time: f64) -> Self {
+        Sample { time, ..self }
+    }
+
+    /// Estimate pressure from drawing speed for devices without a pressure sensor,
+    /// fast movement gives thinner lines, like a real pen.
+    pub fn simulated(previous: Option<&Sample>, point: Point) -> Self {
+        let Some(previous) = previous else {
+            return Sample::new(point, DEFAULT_PRESSURE);
+        };
+        let distance = previous.point.distance(point);
+        let target = 1.0 - (distance / 40.0).min(1.0) * 0.8;
+        // ease toward the target so the width doesn't jump between samples
+        Sample::new(
+            point,
+            previous.pressure + (target - previous.pressure) * 0.3,
+        )
+    }
+
+    /// Time and pressure of the sample a fraction `t` of the way to `other`
+    pub fn lerp(&self, other: &Sample, point: Point, t: f32) -> Self {
+        Sample::new(point, self.pressure + (other.pressure - self.pressure) * t)
+            .at(self.time + (other.time - self.time) * t as f64)
+    }
+}
+
+/// Seconds since the Unix epoch, for timestamping samples
+pub fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0., |since| since.as_secs_f64())
+}
+
+impl From<Point> for Sample {
+    fn from(point: Point) -> Self {
+        Sample::new(point, DEFAULT_PRESSURE)
+    }
+}
+
+type RawCurve = Vec<Sample>;
+
+/// The paths a curve is drawn with, so exports draw exactly what the canvas does
+pub enum Paint<'a> {
+    /// Outlines filled with the curve's color, each filled on its own
+    Fill(Vec<&'a Path>),
+    /// A center line stroked `width` wide, with round caps and joins
+    Stroke { path: &'a Path, width: f32 },
+}
+
+/// Number of samples outlined together before the outline is sealed while drawing
+const CHUNK_LEN: usize = 64;
 
 #[derive(Debug, Clone)]
 pub struct Curve {
     raw: RawCurve,
-    path: Path,
+    /// Outlines of finished runs of `raw`, never rebuilt while the curve grows
+    sealed: Vec<Path>,
+    /// Index of the first sample of the tail, shared with the last sealed run
+    tail_start: usize,
+    /// Outline of the samples from `tail_start` on, rebuilt on each push
+    tail: Path,
+    /// Smooth segments fitted to the ink, when present these are what's drawn and
+    /// `raw` is only their flattened approximation
+    fitted: Option<Vec<CubicBezier>>,
+    /// Parameters of a shape, when present the curve is drawn as its stroked `tail`
+    /// and `raw` is its flattened outline
+    shape: Option<Shape>,
+    /// Bounds of the center line, kept up to date as samples are added
+    index: BoundsIndex,
     color: Color,
     width: f32,
+    layer: LayerId,
 }
 
 impl Default for Curve {
@@ -21,47 +123,656 @@ impl Default for Curve {
 }
 
 impl Curve {
-    pub fn new(raw_curve: Vec<Point>, color: Color) -> Self {
+    pub fn new(raw_curve: Vec<Sample>, color: Color) -> Self {
+        Self::with_width(raw_curve, color, DEFAULT_WIDTH)
+    }
+
+    pub fn with_width(raw_curve: Vec<Sample>, color: Color, width: f32) -> Self {
         Curve {
-            path: Self::build_path(&raw_curve),
+            sealed: vec![],
+            tail_start: 0,
+            tail: Self::build_path(&raw_curve, 0, width),
+            fitted: None,
+            shape: None,
+            index: BoundsIndex::new(raw_curve.iter().map(|s| s.point)),
             raw: raw_curve,
             color,
-            width: 2.0,
+            width,
+            layer: 0,
         }
     }
 
-    fn build_path(raw: &RawCurve) -> Path {
+    /// Create a curve from bézier segments, each starting where the previous one ends
+    pub fn from_beziers(segments: Vec<CubicBezier>, color: Color, width: f32) -> Self {
+        let raw = match segments.first() {
+            Some(first) => std::iter::once(first.from)
+                .chain(segments.iter().flat_map(|s| s.flatten(FLATTEN_STEP)))
+                .collect(),
+            None => vec![],
+        };
+        Curve {
+            sealed: vec![],
+            tail_start: 0,
+            tail: Self::build_bezier_path(&segments, width),
+            fitted: Some(segments),
+            shape: None,
+            index: BoundsIndex::new(raw.iter().map(|s| s.point)),
+            raw,
+            color,
+            width,
+            layer: 0,
+        }
+    }
+
+    /// Create a curve drawn as an exact shape
+    pub fn from_shape(shape: Shape, color: Color, width: f32) -> Self {
+        let raw: RawCurve = shape.flatten().into_iter().map(Sample::from).collect();
+        Curve {
+            sealed: vec![],
+            tail_start: 0,
+            tail: shape.path(),
+            fitted: None,
+            shape: Some(shape),
+            index: BoundsIndex::new(raw.iter().map(|s| s.point)),
+            raw,
+            color,
+            width,
+            layer: 0,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        self.color
+    }
+
+    /// Width of the stroke at full pressure
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// Change the color, the outline stays as it is
+    pub fn set_color(&mut self, color: Color) {
+        self.color = color;
+    }
+
+    /// Change the width at full pressure, rebuilding the outline
+    pub fn set_width(&mut self, width: f32) {
+        self.width = width;
+        match (&self.shape, &self.fitted) {
+            // shapes are stroked as they're drawn, from the new width, see `paint`
+            (Some(_), _) => {}
+            (None, Some(segments)) => self.tail = Self::build_bezier_path(segments, width),
+            (None, None) => {
+                self.sealed.clear();
+                self.tail_start = 0;
+                self.tail = Self::build_path(&self.raw, 0, width);
+            }
+        }
+    }
+
+    pub fn layer(&self) -> LayerId {
+        self.layer
+    }
+
+    /// The same curve on another layer
+    pub fn on_layer(mut self, layer: LayerId) -> Self {
+        self.layer = layer;
+        self
+    }
+
+    /// A copy with its color made more transparent by `opacity`
+    pub fn faded(&self, opacity: f32) -> Self {
+        Curve {
+            color: self.color.scale_alpha(opacity),
+            ..self.clone()
+        }
+    }
+
+    /// When drawing the curve started, 0 if unknown
+    pub fn time(&self) -> f64 {
+        self.raw.first().map_or(0., |sample| sample.time)
+    }
+
+    /// How long drawing the curve took, in seconds
+    pub fn duration(&self) -> f64 {
+        match (self.raw.first(), self.raw.last()) {
+            (Some(first), Some(last)) => (last.time - first.time).max(0.),
+            _ => 0.,
+        }
+    }
+
+    /// The same curve with all its samples drawn at `time`, e.g. a shape that appears at once
+    pub fn stamped(mut self, time: f64) -> Self {
+        self.raw.iter_mut().for_each(|sample| sample.time = time);
+        if let Some(segments) = &mut self.fitted {
+            for segment in segments {
+                segment.from.time = time;
+                segment.to.time = time;
+            }
+        }
+        self
+    }
+
+    /// The part of the curve drawn by `time`, as plain samples
+    pub fn drawn_by(&self, time: f64) -> Self {
+        let drawn = self.raw.partition_point(|sample| sample.time <= time);
+        Self::with_width(self.raw[..drawn].to_vec(), self.color, self.width).on_layer(self.layer)
+    }
+
+    pub fn samples(&self) -> &[Sample] {
+        &self.raw
+    }
+
+    /// Fitted segments, if the curve was fitted
+    pub fn beziers(&self) -> Option<&[CubicBezier]> {
+        self.fitted.as_deref()
+    }
+
+    pub fn shape(&self) -> Option<&Shape> {
+        self.shape.as_ref()
+    }
+
+    /// Replace the parameters of a shape, or turn the curve into one
+    pub fn set_shape(&mut self, shape: Shape) {
+        *self = Self::from_shape(shape, self.color, self.width).on_layer(self.layer);
+    }
+
+    /// The curve moved, scaled and rotated by `transform`. Shapes that can't stay exact
+    /// become plain strokes through their outline.
+    pub fn transformed(&self, transform: &Transform) -> Self {
+        let moved = |sample: &Sample| Sample {
+            point: transform.apply(sample.point),
+            ..*sample
+        };
+        let curve = match (
+            self.shape.and_then(|s| s.transformed(transform)),
+            &self.fitted,
+        ) {
+            (Some(shape), _) => {
+                Self::from_shape(shape, self.color, self.width).stamped(self.time())
+            }
+            (None, Some(segments)) => Self::from_beziers(
+                segments
+                    .iter()
+                    .map(|segment| CubicBezier {
+                        from: moved(&segment.from),
+                        control_a: transform.apply(segment.control_a),
+                        control_b: transform.apply(segment.control_b),
+                        to: moved(&segment.to),
+                    })
+                    .collect(),
+                self.color,
+                self.width,
+            ),
+            (None, None) => {
+                Self::with_width(self.raw.iter().map(moved).collect(), self.color, self.width)
+            }
+        };
+        curve.on_layer(self.layer)
+    }
+
+    /// Width of the stroke at a given sample
+    fn sample_width(width: f32, sample: &Sample) -> f32 {
+        width * (MIN_WIDTH_RATIO + (1.0 - MIN_WIDTH_RATIO) * sample.pressure)
+    }
+
+    /// Pressure a sample of a curve `width` wide needs to be drawn `sample_width` wide,
+    /// the inverse of [`Curve::sample_width`]
+    pub fn pressure_for(width: f32, sample_width: f32) -> f32 {
+        ((sample_width / width - MIN_WIDTH_RATIO) / (1.0 - MIN_WIDTH_RATIO)).clamp(0., 1.)
+    }
+
+    /// How far the edge of the stroke moves per unit of pressure
+    fn pressure_scale(width: f32) -> f32 {
+        (1.0 - MIN_WIDTH_RATIO) * width / 2.
+    }
+
+    /// Build the filled outline of `raw[start..]`, up to and including `end`.
+    ///
+    /// Both sides are offset from the samples along the averaged normal, by half the
+    /// sample width. Ends of the whole stroke get round caps, ends that continue into
+    /// a neighbouring run are cut flat along the normal so the runs meet seamlessly.
+    fn build_run(raw: &[Sample], start: usize, end: usize, width: f32) -> Path {
         Path::new(|builder| {
-            raw.iter().enumerate().for_each(|(i, &p)| match i {
-                0 => builder.move_to(p),
-                _ => builder.line_to(p),
-            })
+            if start == end {
+                let single = &raw[start];
+                builder.circle(single.point, Self::sample_width(width, single) / 2.);
+                return;
+            }
+            let (left, right): (Vec<Point>, Vec<Point>) = (start..=end)
+                .map(|i| {
+                    let sample = &raw[i];
+                    let normal = normal(tangent(raw, i));
+                    let offset = normal * (Self::sample_width(width, sample) / 2.);
+                    (sample.point + offset, sample.point - offset)
+                })
+                .unzip();
+
+            let last = left.len() - 1;
+            builder.move_to(left[0]);
+            smooth_through(builder, &left);
+            if end == raw.len() - 1 {
+                round_cap(builder, raw[end].point, right[last]);
+            }
+            let right: Vec<Point> = right.into_iter().rev().collect();
+            smooth_through(builder, &right);
+            if start == 0 {
+                round_cap(builder, raw[0].point, left[0]);
+            }
+            builder.close();
         })
     }
 
+    /// Build the outline of all samples from `start` to the end of the stroke
+    fn build_path(raw: &[Sample], start: usize, width: f32) -> Path {
+        match raw.len() {
+            0 => Path::new(|_| ()),
+            len => Self::build_run(raw, start, len - 1, width),
+        }
+    }
+
+    /// Build the filled outline of fitted segments.
+    ///
+    /// Each side offsets the segment's control points along the normals at its ends,
+    /// which stays close to the true offset curve for the gentle segments a fit produces.
+    fn build_bezier_path(segments: &[CubicBezier], width: f32) -> Path {
+        let offset_sides = |segment: &CubicBezier| {
+            let chord = segment.to.point - segment.from.point;
+            let start = segment.control_a - segment.from.point;
+            let end = segment.to.point - segment.control_b;
+            let start = normal(unit(if length(start) > 0. { start } else { chord }));
+            let end = normal(unit(if length(end) > 0. { end } else { chord }));
+            let start = start * (Self::sample_width(width, &segment.from) / 2.);
+            let end = end * (Self::sample_width(width, &segment.to) / 2.);
+            let side = |sign: f32| {
+                [
+                    segment.from.point + start * sign,
+                    segment.control_a + start * sign,
+                    segment.control_b + end * sign,
+                    segment.to.point + end * sign,
+                ]
+            };
+            (side(1.), side(-1.))
+        };
+
+        Path::new(|builder| {
+            let (Some(first), Some(last)) = (segments.first(), segments.last()) else {
+                return;
+            };
+            let (left, right): (Vec<[Point; 4]>, Vec<[Point; 4]>) =
+                segments.iter().map(offset_sides).unzip();
+
+            builder.move_to(left[0][0]);
+            for [from, a, b, to] in &left {
+                builder.line_to(*from);
+                builder.bezier_curve_to(*a, *b, *to);
+            }
+            round_cap(builder, last.to.point, right[right.len() - 1][3]);
+            for [from, a, b, to] in right.iter().rev() {
+                builder.line_to(*to);
+                builder.bezier_curve_to(*b, *a, *from);
+            }
+            round_cap(builder, first.from.point, left[0][0]);
+            builder.close();
+        })
+    }
+
+    /// What drawing the curve paints, in world coordinates. Shapes are stroked as wide
+    /// as a pen stroke of the same width drawn at the default pressure, so a shape and a
+    /// stroke drawn with the same width setting look alike.
+    pub fn paint(&self) -> Paint<'_> {
+        match self.shape {
+            Some(_) => Paint::Stroke {
+                path: &self.tail,
+                width: Self::sample_width(self.width, &Sample::from(Point::ORIGIN)),
+            },
+            None => Paint::Fill(self.sealed.iter().chain([&self.tail]).collect()),
+        }
+    }
+
     pub fn draw<Renderer>(&self, frame: &mut Frame<Renderer>)
     where
         Renderer: geometry::Renderer,
     {
-        frame.stroke(
-            &self.path,
-            Stroke::default()
-                .with_color(self.color)
-                .with_width(self.width),
-        )
+        self.draw_with_opacity(frame, 1.);
     }
 
-    pub fn create_reduced(&self, factor: usize) -> Self {
-        Self::new(
-            self.raw.chunks(factor).map(|chunk| chunk[0]).collect(),
+    /// Draw with the color made more transparent by `opacity`
+    pub fn draw_with_opacity<Renderer>(&self, frame: &mut Frame<Renderer>, opacity: f32)
+    where
+        Renderer: geometry::Renderer,
+    {
+        let color = self.color.scale_alpha(opacity);
+        match self.paint() {
+            Paint::Stroke { path, width } => frame.stroke(
+                path,
+                Stroke::default()
+                    .with_color(color)
+                    .with_width(width)
+                    .with_line_cap(LineCap::Round)
+                    .with_line_join(LineJoin::Round),
+            ),
+            Paint::Fill(paths) => paths.into_iter().for_each(|path| frame.fill(path, color)),
+        }
+    }
+
+    /// Simplify the curve with Ramer–Douglas–Peucker, dropping samples that are within
+    /// `tolerance` (in world units) of the simplified stroke, in position and in width.
+    /// The first and last samples are always kept.
+    pub fn simplified(&self, tolerance: f32) -> Self {
+        Self::with_width(
+            simplify(&self.raw, tolerance, self.width),
             self.color,
+            self.width,
         )
+        .on_layer(self.layer)
     }
 
-    pub fn push(&mut self, point: Point) {
-        self.raw.push(point);
-        self.path = Self::build_path(&self.raw)
+    /// Replace the samples with piecewise cubic béziers that stay within `max_error`
+    /// (in world units) of them
+    pub fn fitted(&self, max_error: f32) -> Self {
+        let segments = bezier::fit(&self.raw, max_error, Self::pressure_scale(self.width));
+        match segments.is_empty() {
+            // too few distinct samples to fit, e.g. a dot
+            true => self.clone(),
+            false => Self::from_beziers(segments, self.color, self.width).on_layer(self.layer),
+        }
     }
+
+    /// Append a sample, only the unsealed tail of the outline is rebuilt
+    pub fn push(&mut self, sample: Sample) {
+        self.raw.push(sample);
+        self.index.push(self.raw.len() - 1, sample.point);
+        self.fitted = None;
+        self.shape = None;
+        // A run can be sealed once the sample after it is known, since that fixes
+        // the direction of its last sample
+        if self.raw.len() - self.tail_start > CHUNK_LEN + 1 {
+            let end = self.tail_start + CHUNK_LEN;
+            self.sealed
+                .push(Self::build_run(&self.raw, self.tail_start, end, self.width));
+            self.tail_start = end;
+        }
+        self.tail = Self::build_path(&self.raw, self.tail_start, self.width)
+    }
+
+    /// Number of samples
+    pub fn len(&self) -> usize {
+        self.raw.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.raw.is_empty()
+    }
+
+    pub fn last(&self) -> Option<&Sample> {
+        self.raw.last()
+    }
+
+    /// Bounding box of the drawn stroke, including its width
+    pub fn bounds(&self) -> Option<Rectangle> {
+        self.index
+            .total()
+            .map(|bounds| geo::expand(bounds, self.width / 2.))
+    }
+
+    /// Segments of the center line through the samples in `range`, a curve of a
+    /// single sample is a single segment of zero length
+    fn segments(&self, range: Range<usize>) -> impl Iterator<Item = (Point, Point)> + '_ {
+        let last = self.raw.len() - 1;
+        (range.start..(range.end - 1).max(range.start + 1))
+            .map(move |i| (self.raw[i].point, self.raw[(i + 1).min(last)].point))
+    }
+
+    /// Segments of runs whose bounds pass `test`
+    fn segments_where<'a>(
+        &'a self,
+        test: impl Fn(&Rectangle) -> bool + 'a,
+    ) -> impl Iterator<Item = (Point, Point)> + 'a {
+        self.index
+            .runs(self.raw.len())
+            .filter(move |(bounds, _)| test(bounds))
+            .flat_map(|(_, range)| self.segments(range))
+    }
+
+    /// Distance from `point` to the center line of the curve, infinite for an empty curve
+    pub fn distance(&self, point: Point) -> f32 {
+        let mut best = f32::INFINITY;
+        for (bounds, range) in self.index.runs(self.raw.len()) {
+            if geo::rectangle_distance(bounds, point) >= best {
+                continue;
+            }
+            for (a, b) in self.segments(range) {
+                best = best.min(geo::segment_distance(point, a, b));
+            }
+        }
+        best
+    }
+
+    /// Whether the center line touches the segment `a`..`b`
+    pub fn intersects_segment(&self, a: Point, b: Point) -> bool {
+        let Some(segment) = geo::polygon_bounds(&[a, b]) else {
+            return false;
+        };
+        self.segments_where(move |bounds| geo::overlaps(bounds, &segment))
+            .any(|(c, d)| geo::segments_intersect(a, b, c, d))
+    }
+
+    /// Whether the drawn stroke, including its width, comes within `distance` of the
+    /// segment `a`..`b`
+    pub fn touches(&self, a: Point, b: Point, distance: f32) -> bool {
+        let reach = distance + self.width / 2.;
+        let Some(segment) = geo::polygon_bounds(&[a, b]) else {
+            return false;
+        };
+        let search = geo::expand(segment, reach);
+        self.segments_where(move |bounds| geo::overlaps(bounds, &search))
+            .any(|(c, d)| geo::segments_distance(a, b, c, d) <= reach)
+    }
+
+    /// Cut away the parts of the stroke within `radius` of the eraser moving from `from`
+    /// to `to`. Returns the pieces left over, with the curve's style, or `None` if the
+    /// eraser didn't take anything away.
+    pub fn erased(&self, from: Point, to: Point, radius: f32) -> Option<Vec<Curve>> {
+        if !self.touches(from, to, radius) {
+            return None;
+        }
+        let reach = radius + self.width / 2.;
+        let is_erased = |sample: &Sample| {
+            geo::segment_distance(sample.point, from, to)
+                <= radius + Self::sample_width(self.width, sample) / 2.
+        };
+        // Segments near the eraser are subdivided, so the cut isn't limited to
+        // where the samples happen to be
+        let step = (radius / 4.).max(0.1);
+        let dense = self
+            .segments(0..self.raw.len())
+            .enumerate()
+            .flat_map(|(i, (a, b))| {
+                let (start, end) = (self.raw[i], self.raw[(i + 1).min(self.raw.len() - 1)]);
+                let steps = match geo::segments_distance(a, b, from, to) <= reach {
+                    true => ((a.distance(b) / step).ceil() as usize).max(1),
+                    false => 1,
+                };
+                (0..steps).map(move |k| {
+                    let t = k as f32 / steps as f32;
+                    start.lerp(&end, a + (b - a) * t, t)
+                })
+            });
+
+        let mut pieces = vec![];
+        let mut piece = vec![];
+        let mut erased = false;
+        for sample in dense.chain(self.raw.last().copied()) {
+            if is_erased(&sample) {
+                erased = true;
+                if piece.len() > 1 {
+                    pieces
+                        .push(Self::with_width(piece, self.color, self.width).on_layer(self.layer));
+                }
+                piece = vec![];
+            } else {
+                piece.push(sample);
+            }
+        }
+        if piece.len() > 1 {
+            pieces.push(Self::with_width(piece, self.color, self.width).on_layer(self.layer));
+        }
+        // only touching the outline between samples leaves the curve as it is
+        erased.then_some(pieces)
+    }
+
+    /// Whether any part of the center line is in `rect`
+    pub fn intersects_rectangle(&self, rect: &Rectangle) -> bool {
+        let area = *rect;
+        self.segments_where(move |bounds| geo::overlaps(bounds, &area))
+            .any(|(a, b)| geo::segment_intersects_rectangle(a, b, rect))
+    }
+
+    /// Whether any part of the center line is inside the closed `polygon`
+    pub fn intersects_polygon(&self, polygon: &[Point]) -> bool {
+        match self.raw.first() {
+            Some(first) => {
+                geo::point_in_polygon(first.point, polygon) || self.crosses_polygon(polygon)
+            }
+            None => false,
+        }
+    }
+
+    /// Whether the whole center line is inside the closed `polygon`
+    pub fn is_within_polygon(&self, polygon: &[Point]) -> bool {
+        match self.raw.first() {
+            Some(first) => {
+                geo::point_in_polygon(first.point, polygon) && !self.crosses_polygon(polygon)
+            }
+            None => false,
+        }
+    }
+
+    fn crosses_polygon(&self, polygon: &[Point]) -> bool {
+        let Some(outline) = geo::polygon_bounds(polygon) else {
+            return false;
+        };
+        self.segments_where(move |bounds| geo::overlaps(bounds, &outline))
+            .any(|(a, b)| geo::segment_crosses_polygon(a, b, polygon))
+    }
+}
+
+/// Ramer–Douglas–Peucker over the samples, without recursion so long strokes can't
+/// overflow the stack
+fn simplify(raw: &[Sample], tolerance: f32, width: f32) -> RawCurve {
+    if raw.len() < 3 {
+        return raw.to_vec();
+    }
+    let mut keep = vec![false; raw.len()];
+    keep[0] = true;
+    keep[raw.len() - 1] = true;
+
+    let mut spans = vec![(0, raw.len() - 1)];
+    while let Some((first, last)) = spans.pop() {
+        let (start, end) = (&raw[first], &raw[last]);
+        let farthest = (first + 1..last)
+            .map(|i| {
+                (
+                    i,
+                    deviation(start, end, &raw[i], i - first, last - first, width),
+                )
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+
+        if let Some((i, error)) = farthest {
+            if error > tolerance {
+                keep[i] = true;
+                spans.push((first, i));
+                spans.push((i, last));
+            }
+        }
+    }
+
+    raw.iter()
+        .zip(keep)
+        .filter_map(|(sample, keep)| keep.then_some(*sample))
+        .collect()
+}
+
+/// How far `sample` strays from the segment `start`..`end`, either in position
+/// or in the edge of the stroke when its width differs from the interpolated width
+fn deviation(
+    start: &Sample,
+    end: &Sample,
+    sample: &Sample,
+    step: usize,
+    steps: usize,
+    width: f32,
+) -> f32 {
+    let distance = geo::segment_distance(sample.point, start.point, end.point);
+    let t = step as f32 / steps as f32;
+    let pressure = start.pressure + (end.pressure - start.pressure) * t;
+    let width_error = (sample.pressure - pressure).abs() * Curve::pressure_scale(width);
+    distance.max(width_error)
+}
+
+/// Unit direction of travel at sample `i`, averaged over its neighbours
+fn tangent(raw: &[Sample], i: usize) -> Vector {
+    let before = raw[i.saturating_sub(1)].point;
+    let after = raw[(i + 1).min(raw.len() - 1)].point;
+    let mut direction = after - before;
+    // step out further if neighbours coincide
+    let mut reach = 2;
+    while length(direction) == 0.0 && reach < raw.len() {
+        direction = raw[(i + reach).min(raw.len() - 1)].point - raw[i.saturating_sub(reach)].point;
+        reach += 1;
+    }
+    match length(direction) {
+        0.0 => Vector::new(1.0, 0.0),
+        _ => unit(direction),
+    }
+}
+
+fn unit(v: Vector) -> Vector {
+    v * (1.0 / length(v))
+}
+
+fn normal(tangent: Vector) -> Vector {
+    Vector::new(-tangent.y, tangent.x)
+}
+
+fn length(v: Vector) -> f32 {
+    v.x.hypot(v.y)
+}
+
+/// Continue the current sub path through `points`, using the inner points as
+/// quadratic control points and their midpoints as knots
+fn smooth_through(builder: &mut Builder, points: &[Point]) {
+    let last = points.len() - 1;
+    builder.line_to(points[0]);
+    for i in 1..last {
+        let mid = Point::new(
+            (points[i].x + points[i + 1].x) / 2.,
+            (points[i].y + points[i + 1].y) / 2.,
+        );
+        builder.quadratic_curve_to(points[i], mid);
+    }
+    builder.line_to(points[last]);
+}
+
+/// Half circle from the current position around `center` to the opposite point `to`
+fn round_cap(builder: &mut Builder, center: Point, to: Point) {
+    let back = to - center;
+    let radius = length(back);
+    if radius == 0.0 {
+        builder.line_to(to);
+        return;
+    }
+    let side = back * -1.0;
+    let forward = Vector::new(-back.y, back.x);
+    let k = KAPPA;
+    builder.bezier_curve_to(
+        center + side + forward * k,
+        center + forward + side * k,
+        center + forward,
+    );
+    builder.bezier_curve_to(center + forward + back * k, center + back + forward * k, to);
 }
 
 pub mod curve_demo {
@@ -142,3 +853,171 @@ pub mod curve_demo {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iced::Size;
+
+    /// Samples along a sine wave, `count` of them one unit apart in x
+    fn wave(count: usize, pressure: f32) -> Vec<Sample> {
+        (0..count)
+            .map(|i| {
+                let x = i as f32;
+                Sample::new(Point::new(x, (x / 8.).sin() * 20.), pressure)
+            })
+            .collect()
+    }
+
+    /// Distance from `point` to the polyline through `samples`
+    fn polyline_distance(samples: &[Sample], point: Point) -> f32 {
+        samples
+            .windows(2)
+            .map(|pair| geo::segment_distance(point, pair[0].point, pair[1].point))
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    #[test]
+    fn simplify_stays_within_tolerance() {
+        let raw = wave(200, 0.5);
+        let tolerance = 0.5;
+        let simplified = simplify(&raw, tolerance, DEFAULT_WIDTH);
+        assert!(simplified.len() < raw.len() / 4);
+        assert_eq!(simplified.first(), raw.first());
+        assert_eq!(simplified.last(), raw.last());
+        for sample in &raw {
+            assert!(polyline_distance(&simplified, sample.point) <= tolerance);
+        }
+    }
+
+    #[test]
+    fn simplify_straight_line_to_its_ends() {
+        let raw: Vec<Sample> = (0..50)
+            .map(|i| Sample::new(Point::new(i as f32, i as f32 * 2.), 0.5))
+            .collect();
+        let simplified = simplify(&raw, 0.1, DEFAULT_WIDTH);
+        assert_eq!(simplified, [raw[0], raw[49]]);
+    }
+
+    #[test]
+    fn fitted_curve_stays_within_max_error() {
+        let curve = Curve::with_width(wave(200, 0.5), Color::WHITE, DEFAULT_WIDTH);
+        let fitted = curve.fitted(0.5);
+        assert!(fitted.beziers().is_some());
+        assert_eq!(fitted.samples().first(), curve.samples().first());
+        assert_eq!(fitted.samples().last(), curve.samples().last());
+        // the samples kept are a step apart along the fit, so lines between them cut
+        // corners a little
+        for sample in curve.samples() {
+            assert!(polyline_distance(fitted.samples(), sample.point) <= 0.5 + 0.1);
+        }
+    }
+
+    #[test]
+    fn fitted_dot_stays_a_dot() {
+        let dot = Sample::new(Point::new(3., 4.), 0.5);
+        let curve = Curve::with_width(vec![dot, dot], Color::WHITE, DEFAULT_WIDTH);
+        let fitted = curve.fitted(0.5);
+        assert!(fitted.beziers().is_none());
+        assert_eq!(fitted.samples(), curve.samples());
+    }
+
+    #[test]
+    fn simplify_keeps_changes_in_width() {
+        // a straight line that swells in the middle
+        let raw: Vec<Sample> = (0..=20)
+            .map(|i| {
+                let pressure = 1. - (i as f32 - 10.).abs() / 10.;
+                Sample::new(Point::new(i as f32, 0.), pressure)
+            })
+            .collect();
+        let simplified = simplify(&raw, 0.5, 10.);
+        assert!(simplified.contains(&raw[10]));
+    }
+
+    #[test]
+    fn queries_reach_every_run() {
+        // long enough to span several runs of the bounds index
+        let samples = (0..300)
+            .map(|i| Sample::new(Point::new(i as f32, 0.), 0.5))
+            .collect();
+        let curve = Curve::with_width(samples, Color::WHITE, 4.);
+        assert_eq!(
+            curve.bounds(),
+            Some(Rectangle::new(Point::new(-2., -2.), Size::new(303., 4.)))
+        );
+        assert_eq!(curve.distance(Point::new(250., 3.)), 3.);
+        assert!(curve.intersects_segment(Point::new(290., -5.), Point::new(290., 5.)));
+        assert!(curve.touches(Point::new(290., 4.), Point::new(295., 4.), 2.));
+        assert!(!curve.touches(Point::new(290., 5.), Point::new(295., 5.), 2.));
+
+        let end = Rectangle::new(Point::new(280., -1.), Size::new(5., 2.));
+        assert!(curve.intersects_rectangle(&end));
+        let past = Rectangle::new(Point::new(310., -1.), Size::new(5., 2.));
+        assert!(!curve.intersects_rectangle(&past));
+
+        let around = |from: f32, to: f32| {
+            [
+                Point::new(from, -1.),
+                Point::new(to, -1.),
+                Point::new(to, 1.),
+                Point::new(from, 1.),
+            ]
+        };
+        assert!(curve.intersects_polygon(&around(200., 210.)));
+        assert!(!curve.is_within_polygon(&around(200., 210.)));
+        assert!(curve.is_within_polygon(&around(-1., 300.)));
+    }
+
+    /// A straight stroke from (0, 0) to (100, 0), 10 wide at full pressure
+    fn line(pressure: f32) -> Curve {
+        let samples = (0..=10)
+            .map(|i| Sample::new(Point::new(i as f32 * 10., 0.), pressure))
+            .collect();
+        Curve::with_width(samples, Color::from_rgb(1., 0., 0.), 10.).on_layer(3)
+    }
+
+    #[test]
+    fn erasing_across_the_middle_splits() {
+        let curve = line(1.);
+        let pieces = curve
+            .erased(Point::new(50., -20.), Point::new(50., 20.), 2.)
+            .expect("erased");
+        assert_eq!(pieces.len(), 2);
+        for piece in &pieces {
+            assert_eq!(piece.color(), curve.color());
+            assert_eq!(piece.width(), curve.width());
+            assert_eq!(piece.layer(), curve.layer());
+        }
+        // cut where the eraser's edge meets the stroke's, not at the nearest samples
+        let left = pieces[0].last().expect("a sample").point.x;
+        let right = pieces[1].samples()[0].point.x;
+        assert!((40. ..48.).contains(&left), "{left}");
+        assert!((52. ..60.).contains(&right), "{right}");
+    }
+
+    #[test]
+    fn erasing_everything_leaves_nothing() {
+        let pieces = line(1.)
+            .erased(Point::new(-10., 0.), Point::new(110., 0.), 2.)
+            .expect("erased");
+        assert!(pieces.is_empty());
+    }
+
+    #[test]
+    fn erasing_past_the_stroke_misses() {
+        assert!(line(1.)
+            .erased(Point::new(0., 20.), Point::new(100., 20.), 2.)
+            .is_none());
+    }
+
+    #[test]
+    fn grazing_the_outline_leaves_the_curve() {
+        // the eraser's edge dips just past the stroke's, between two of the points the
+        // stroke is cut at, which are a quarter of the radius apart
+        let curve = line(1.);
+        let at = Point::new(50.25, 6.999);
+        assert!(curve.touches(at, at, 2.));
+        assert!(curve.erased(at, at, 2.).is_none());
+    }
+}
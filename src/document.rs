@@ -0,0 +1,689 @@
+//! The on disk document format
+//!
+//! Documents are JSON:
+//!
+//! ```json
+//! {
+//!   "format": "monstera",
+//!   "version": 4,
+//!   "camera": [x, y, z],
+//!   "palette": [[r, g, b, a], ...],
+//!   "active_color": [r, g, b, a],
+//!   "curves": [{ "color": [r, g, b, a], "width": 2.0, "time": t, "layer": 0, "kind": ..., ... }, ...],
+//!   "history": { "current": 0, "nodes": [{ "parent": null, "splices": [...] }, ...], "curves": [...] },
+//!   "layers": [{ "id": 0, "name": "Layer 1", "visible": true, "locked": false, "opacity": 1.0 }, ...],
+//!   "active_layer": 0,
+//!   "next_layer_id": 1
+//! }
+//! ```
+//!
+//! Colors are iced's non-linear `Color` channels. `time` is when drawing the curve
+//! started in seconds since the Unix epoch, 0 if unknown, and `dt` of a sample is the
+//! seconds from then until it was drawn. Each curve is stored as one of
+//! - `"kind": "samples"` with `"samples": [[x, y, pressure, dt], ...]`
+//! - `"kind": "beziers"` with `"start": [x, y, pressure, dt]` and
+//!   `"segments": [[control_a x, y, control_b x, y, end x, y, pressure, dt], ...]`
+//! - `"kind": "shape"` with `"shape": { "type": "rectangle" | "ellipse" | "arrow" | "polygon", ... }`
+//!
+//! `layers` are listed bottom first, and `layer` of a curve is the `id` of the layer it's
+//! on. Without layers everything is on a single layer with id 0. `next_layer_id` is
+//! the id the next layer added gets, ids of deleted layers aren't reused.
+//!
+//! `history` is optional. It's the tree of edits that led to `curves`, each node
+//! reached from its `parent` by applying its splices
+//! `{ "index": 0, "removed": [i, ...], "inserted": [i, ...] }` in order, and `current`
+//! is the node `curves` are at. The splices refer to curves by index, indices below the
+//! number of `curves` are the document's curves and the ones after continue into the
+//! history's own `curves`, so a curve is stored once however many splices hold on to it.
+//! A node that deleted a layer also has `"removal": { "position": 0, "layer": layer }`,
+//! with the position of the layer bottom first.
+//!
+//! `version` is bumped whenever the format changes, older versions are migrated
+//! on load and newer versions are refused.
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use iced::{Color, Point, Vector};
+use serde::{Deserialize, Serialize};
+
+use crate::bezier::CubicBezier;
+use crate::curve::{Curve, Sample};
+use crate::edit::Splice;
+use crate::layer::{Layer, LayerId, Layers, Removal};
+use crate::shape::Shape;
+
+pub const FORMAT: &str = "monstera";
+pub const FORMAT_VERSION: u32 = 4;
+pub const EXTENSION: &str = "monstera";
+
+#[derive(Debug)]
+pub enum DocumentError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+    /// The file isn't a monstera document at all
+    NotADocument,
+    /// Saved by a newer monstera than this one
+    TooNew {
+        version: u32,
+    },
+    /// Saved in an old format that can no longer be read
+    Unsupported {
+        version: u32,
+    },
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocumentError::Io(error) => write!(f, "could not access the file: {error}"),
+            DocumentError::Parse(error) => write!(f, "the document is damaged: {error}"),
+            DocumentError::NotADocument => write!(f, "not a monstera document"),
+            DocumentError::TooNew { version } => write!(
+                f,
+                "the document uses format version {version}, which is newer than this \
+                monstera supports (version {FORMAT_VERSION}), please update monstera"
+            ),
+            DocumentError::Unsupported { version } => write!(
+                f,
+                "the document uses format version {version}, which this monstera can no \
+                longer read"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DocumentError {}
+
+impl From<io::Error> for DocumentError {
+    fn from(error: io::Error) -> Self {
+        DocumentError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for DocumentError {
+    fn from(error: serde_json::Error) -> Self {
+        DocumentError::Parse(error)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Document {
+    pub format: String,
+    pub version: u32,
+    pub camera: [f32; 3],
+    pub palette: Vec<[f32; 4]>,
+    pub active_color: [f32; 4],
+    pub curves: Vec<CurveData>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub history: Option<HistoryData>,
+    #[serde(default)]
+    pub layers: Vec<Layer>,
+    #[serde(default)]
+    pub active_layer: LayerId,
+    #[serde(default)]
+    pub next_layer_id: LayerId,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryData {
+    /// Node the curves are at
+    pub current: usize,
+    /// The root first, every other node after its parent
+    pub nodes: Vec<NodeData>,
+    /// Curves the splices refer to that aren't among the document's curves
+    #[serde(default)]
+    pub curves: Vec<CurveData>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeData {
+    pub parent: Option<usize>,
+    pub splices: Vec<SpliceData>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub removal: Option<Removal>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpliceData {
+    pub index: usize,
+    /// Indices of curves, the document's followed by the history's
+    pub removed: Vec<usize>,
+    pub inserted: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CurveData {
+    pub color: [f32; 4],
+    pub width: f32,
+    #[serde(default)]
+    pub time: f64,
+    #[serde(default)]
+    pub layer: LayerId,
+    #[serde(flatten)]
+    pub geometry: GeometryData,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum GeometryData {
+    Samples {
+        samples: Vec<[f32; 4]>,
+    },
+    Beziers {
+        start: [f32; 4],
+        segments: Vec<[f32; 8]>,
+    },
+    Shape {
+        shape: ShapeData,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ShapeData {
+    Rectangle {
+        center: [f32; 2],
+        half: [f32; 2],
+        rotation: f32,
+    },
+    Ellipse {
+        center: [f32; 2],
+        radii: [f32; 2],
+        rotation: f32,
+    },
+    Arrow {
+        from: [f32; 2],
+        to: [f32; 2],
+        head: f32,
+    },
+    Polygon {
+        center: [f32; 2],
+        radii: [f32; 2],
+        rotation: f32,
+        sides: u32,
+    },
+}
+
+impl Document {
+    pub fn new(camera: [f32; 3], palette: &[Color], active_color: Color, curves: &[Curve]) -> Self {
+        Document {
+            format: FORMAT.into(),
+            version: FORMAT_VERSION,
+            camera,
+            palette: palette.iter().map(|c| color_data(*c)).collect(),
+            active_color: color_data(active_color),
+            curves: curves.iter().map(CurveData::from).collect(),
+            history: None,
+            layers: vec![],
+            active_layer: 0,
+            next_layer_id: 0,
+        }
+    }
+
+    pub fn with_layers(mut self, layers: &Layers) -> Self {
+        self.layers = layers.iter().cloned().collect();
+        self.active_layer = layers.active().id;
+        self.next_layer_id = layers.next_id();
+        self
+    }
+
+    pub fn layers(&self) -> Layers {
+        // layers deleted in the history keep their ids too
+        let removed = self
+            .history
+            .iter()
+            .flat_map(|history| &history.nodes)
+            .filter_map(|node| node.removal.as_ref())
+            .map(|removal| removal.layer.id + 1);
+        let next_id = removed.fold(self.next_layer_id, LayerId::max);
+        Layers::new(self.layers.clone(), self.active_layer, next_id)
+    }
+
+    pub fn with_history(mut self, history: HistoryData) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    pub fn palette(&self) -> Vec<Color> {
+        self.palette.iter().map(|&c| Color::from(c)).collect()
+    }
+
+    pub fn active_color(&self) -> Color {
+        Color::from(self.active_color)
+    }
+
+    pub fn curves(&self) -> Vec<Curve> {
+        self.curves.iter().map(Curve::from).collect()
+    }
+
+    pub fn to_json(&self) -> Result<String, DocumentError> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Parse a document, migrating it from older format versions
+    pub fn from_json(json: &str) -> Result<Self, DocumentError> {
+        let value: serde_json::Value = serde_json::from_str(json)?;
+        if value.get("format").and_then(|f| f.as_str()) != Some(FORMAT) {
+            return Err(DocumentError::NotADocument);
+        }
+        let version = value
+            .get("version")
+            .and_then(|v| v.as_u64())
+            .ok_or(DocumentError::NotADocument)? as u32;
+
+        check_version(version)?;
+
+        // version 2 added the optional history, the rest only changed how curves are stored
+        let mut value = value;
+        if version < FORMAT_VERSION {
+            migrate_curves(&mut value["curves"], version);
+            if let Some(curves) = value.get_mut("history").and_then(|h| h.get_mut("curves")) {
+                migrate_curves(curves, version);
+            }
+            value["version"] = FORMAT_VERSION.into();
+        }
+        Ok(serde_json::from_value(value)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, DocumentError> {
+        Self::from_json(&fs::read_to_string(path)?)
+    }
+
+    /// Write the document next to `path` first and move it into place, so a failed
+    /// save can't destroy the previous version
+    pub fn save(&self, path: &Path) -> Result<(), DocumentError> {
+        let json = self.to_json()?;
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        let mut file = tempfile::NamedTempFile::new_in(directory)?;
+        file.write_all(json.as_bytes())?;
+        file.as_file().sync_all()?;
+        file.persist(path).map_err(|error| error.error)?;
+        Ok(())
+    }
+}
+
+/// Whether curves and documents of format `version` can be read, after migrating them
+pub fn check_version(version: u32) -> Result<(), DocumentError> {
+    match version {
+        1..=FORMAT_VERSION => Ok(()),
+        version if version > FORMAT_VERSION => Err(DocumentError::TooNew { version }),
+        // there's no version before the first
+        version => Err(DocumentError::Unsupported { version }),
+    }
+}
+
+/// Bring a JSON list of curves stored in format `version` up to the current format,
+/// the version must have passed [`check_version`]
+pub fn migrate_curves(curves: &mut serde_json::Value, version: u32) {
+    // version 3 added the times of samples, version 4 layers, curves without a layer
+    // are on the default one
+    if version < 3 {
+        untimed_curves(curves);
+    }
+}
+
+/// Give the samples of curves from before version 3 an unknown time
+fn untimed_curves(curves: &mut serde_json::Value) {
+    let untimed = |sample: &mut serde_json::Value| {
+        if let Some(sample) = sample.as_array_mut() {
+            sample.push(0.into());
+        }
+    };
+    for curve in curves.as_array_mut().into_iter().flatten() {
+        for key in ["samples", "segments"] {
+            if let Some(samples) = curve[key].as_array_mut() {
+                samples.iter_mut().for_each(untimed);
+            }
+        }
+        if let Some(start) = curve.get_mut("start") {
+            untimed(start);
+        }
+    }
+}
+
+impl From<&Curve> for CurveData {
+    fn from(curve: &Curve) -> Self {
+        let time = curve.time();
+        let geometry = match (curve.shape(), curve.beziers()) {
+            (Some(shape), _) => GeometryData::Shape {
+                shape: ShapeData::from(*shape),
+            },
+            (None, Some(segments)) if !segments.is_empty() => GeometryData::Beziers {
+                start: sample_data(&segments[0].from, time),
+                segments: segments
+                    .iter()
+                    .map(|s| {
+                        [
+                            s.control_a.x,
+                            s.control_a.y,
+                            s.control_b.x,
+                            s.control_b.y,
+                            s.to.point.x,
+                            s.to.point.y,
+                            s.to.pressure,
+                            (s.to.time - time) as f32,
+                        ]
+                    })
+                    .collect(),
+            },
+            _ => GeometryData::Samples {
+                samples: curve
+                    .samples()
+                    .iter()
+                    .map(|sample| sample_data(sample, time))
+                    .collect(),
+            },
+        };
+        CurveData {
+            color: color_data(curve.color()),
+            width: curve.width(),
+            time,
+            layer: curve.layer(),
+            geometry,
+        }
+    }
+}
+
+impl From<&CurveData> for Curve {
+    fn from(data: &CurveData) -> Self {
+        let color = Color::from(data.color);
+        let time = data.time;
+        let curve = match &data.geometry {
+            GeometryData::Samples { samples } => Curve::with_width(
+                samples.iter().map(|s| sample(s, time)).collect(),
+                color,
+                data.width,
+            ),
+            GeometryData::Beziers { start, segments } => {
+                let mut from = sample(start, time);
+                let segments = segments
+                    .iter()
+                    .map(|&[ax, ay, bx, by, x, y, pressure, dt]| {
+                        let segment = CubicBezier {
+                            from,
+                            control_a: Point::new(ax, ay),
+                            control_b: Point::new(bx, by),
+                            to: sample(&[x, y, pressure, dt], time),
+                        };
+                        from = segment.to;
+                        segment
+                    })
+                    .collect();
+                Curve::from_beziers(segments, color, data.width)
+            }
+            GeometryData::Shape { shape } => {
+                Curve::from_shape(Shape::from(shape), color, data.width).stamped(time)
+            }
+        };
+        curve.on_layer(data.layer)
+    }
+}
+
+/// Curves of a history being saved, each stored once however many splices refer to
+/// it, and not at all if it's among the document's curves
+pub struct CurveTable {
+    /// Number of the document's curves, the table's own curves are indexed after them
+    shared: usize,
+    curves: Vec<CurveData>,
+    /// Index of every curve by its JSON, equal curves are saved the same way
+    indices: HashMap<String, usize>,
+}
+
+impl CurveTable {
+    /// A table for the history of a document with `curves`
+    pub fn new(curves: &[CurveData]) -> Self {
+        let mut indices = HashMap::with_capacity(curves.len());
+        for (index, curve) in curves.iter().enumerate() {
+            indices.entry(json(curve)).or_insert(index);
+        }
+        CurveTable {
+            shared: curves.len(),
+            curves: vec![],
+            indices,
+        }
+    }
+
+    pub fn splice(&mut self, splice: &Splice) -> SpliceData {
+        SpliceData {
+            index: splice.index,
+            removed: splice.removed.iter().map(|c| self.index(c)).collect(),
+            inserted: splice.inserted.iter().map(|c| self.index(c)).collect(),
+        }
+    }
+
+    fn index(&mut self, curve: &Curve) -> usize {
+        let data = CurveData::from(curve);
+        let index = self.shared + self.curves.len();
+        *self.indices.entry(json(&data)).or_insert_with(|| {
+            self.curves.push(data);
+            index
+        })
+    }
+
+    /// The curves to save with the history
+    pub fn into_curves(self) -> Vec<CurveData> {
+        self.curves
+    }
+}
+
+fn json(curve: &CurveData) -> String {
+    serde_json::to_string(curve).expect("Serialize curve")
+}
+
+impl SpliceData {
+    /// The splice with the curves its indices refer to, `None` if one is out of range
+    pub fn splice(&self, curves: &[Curve]) -> Option<Splice> {
+        let curves = |indices: &[usize]| -> Option<Vec<Curve>> {
+            indices.iter().map(|&i| curves.get(i).cloned()).collect()
+        };
+        Some(Splice {
+            index: self.index,
+            removed: curves(&self.removed)?,
+            inserted: curves(&self.inserted)?,
+        })
+    }
+}
+
+impl From<Shape> for ShapeData {
+    fn from(shape: Shape) -> Self {
+        match shape {
+            Shape::Rectangle {
+                center,
+                half,
+                rotation,
+            } => ShapeData::Rectangle {
+                center: center.into(),
+                half: [half.x, half.y],
+                rotation,
+            },
+            Shape::Ellipse {
+                center,
+                radii,
+                rotation,
+            } => ShapeData::Ellipse {
+                center: center.into(),
+                radii: [radii.x, radii.y],
+                rotation,
+            },
+            Shape::Arrow { from, to, head } => ShapeData::Arrow {
+                from: from.into(),
+                to: to.into(),
+                head,
+            },
+            Shape::Polygon {
+                center,
+                radii,
+                rotation,
+                sides,
+            } => ShapeData::Polygon {
+                center: center.into(),
+                radii: [radii.x, radii.y],
+                rotation,
+                sides,
+            },
+        }
+    }
+}
+
+impl From<&ShapeData> for Shape {
+    fn from(data: &ShapeData) -> Self {
+        let vector = |[x, y]: [f32; 2]| Vector::new(x, y);
+        match *data {
+            ShapeData::Rectangle {
+                center,
+                half,
+                rotation,
+            } => Shape::Rectangle {
+                center: center.into(),
+                half: vector(half),
+                rotation,
+            },
+            ShapeData::Ellipse {
+                center,
+                radii,
+                rotation,
+            } => Shape::Ellipse {
+                center: center.into(),
+                radii: vector(radii),
+                rotation,
+            },
+            ShapeData::Arrow { from, to, head } => Shape::Arrow {
+                from: from.into(),
+                to: to.into(),
+                head,
+            },
+            ShapeData::Polygon {
+                center,
+                radii,
+                rotation,
+                sides,
+            } => Shape::Polygon {
+                center: center.into(),
+                radii: vector(radii),
+                rotation,
+                sides: sides.max(3),
+            },
+        }
+    }
+}
+
+fn color_data(color: Color) -> [f32; 4] {
+    [color.r, color.g, color.b, color.a]
+}
+
+/// A sample drawn `dt` seconds after its curve started at `time`
+fn sample_data(sample: &Sample, time: f64) -> [f32; 4] {
+    let dt = (sample.time - time) as f32;
+    [sample.point.x, sample.point.y, sample.pressure, dt]
+}
+
+fn sample(&[x, y, pressure, dt]: &[f32; 4], time: f64) -> Sample {
+    Sample::new(Point::new(x, y), pressure).at(time + dt as f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A curve as versions before 3 stored it, without times
+    const UNTIMED_CURVE: &str = r#"{ "color": [1, 1, 1, 1], "width": 2, "kind": "samples",
+        "samples": [[0, 0, 0.5], [1, 1, 0.5]] }"#;
+
+    fn document(version: u32, history: &str) -> String {
+        format!(
+            r#"{{ "format": "monstera", "version": {version}, "camera": [0, 0, 300],
+            "palette": [[1, 0, 0, 1]], "active_color": [1, 0, 0, 1],
+            "curves": [{UNTIMED_CURVE}] {history} }}"#
+        )
+    }
+
+    #[test]
+    fn migrates_version_1() {
+        let document = Document::from_json(&document(1, "")).expect("a document");
+        assert_eq!(document.version, FORMAT_VERSION);
+        assert!(document.history.is_none());
+        assert!(document.layers.is_empty());
+
+        let curves = document.curves();
+        assert_eq!(curves.len(), 1);
+        assert_eq!(curves[0].layer(), 0);
+        assert!(curves[0].samples().iter().all(|s| s.time == 0.));
+        let layers = document.layers();
+        assert_eq!(layers.iter().count(), 1);
+        assert_eq!(layers.active().id, 0);
+    }
+
+    #[test]
+    fn migrates_the_history_of_version_2() {
+        let history = format!(
+            r#", "history": {{ "current": 1, "nodes": [
+                {{ "parent": null, "splices": [] }},
+                {{ "parent": 0, "splices": [{{ "index": 0, "removed": [1], "inserted": [0] }}] }}
+            ], "curves": [{UNTIMED_CURVE}] }}"#
+        );
+        let document = Document::from_json(&document(2, &history)).expect("a document");
+        let history = document.history.as_ref().expect("a history");
+        let GeometryData::Samples { samples } = &history.curves[0].geometry else {
+            panic!("samples expected");
+        };
+        assert!(samples.iter().all(|s| s[3] == 0.));
+
+        let curves = document.curves();
+        let table: Vec<Curve> = curves.iter().chain(&curves).cloned().collect();
+        assert!(history.nodes[1].splices[0].splice(&table).is_some());
+        // the history's own curves follow the document's
+        assert!(history.nodes[1].splices[0].splice(&curves).is_none());
+    }
+
+    #[test]
+    fn round_trips() {
+        let curves = Document::from_json(&document(1, ""))
+            .expect("a document")
+            .curves();
+        let document = Document::new([1., 2., 3.], &[Color::BLACK], Color::BLACK, &curves);
+        let json = document.to_json().expect("json");
+        let loaded = Document::from_json(&json).expect("a document");
+        assert_eq!(loaded.camera, [1., 2., 3.]);
+        assert_eq!(loaded.curves().len(), 1);
+        assert_eq!(json, loaded.to_json().expect("json"));
+    }
+
+    #[test]
+    fn refuses_other_versions() {
+        let error = Document::from_json(&document(99, "")).expect_err("too new");
+        assert!(matches!(error, DocumentError::TooNew { version: 99 }));
+        let error = Document::from_json(&document(0, "")).expect_err("too old");
+        assert!(matches!(error, DocumentError::Unsupported { version: 0 }));
+        let json = document(1, "").replace("monstera", "other");
+        let error = Document::from_json(&json).expect_err("not a document");
+        assert!(matches!(error, DocumentError::NotADocument));
+    }
+
+    #[test]
+    fn stores_equal_curves_once() {
+        let curves = Document::from_json(&document(1, ""))
+            .expect("a document")
+            .curves();
+        let saved: Vec<CurveData> = curves.iter().map(CurveData::from).collect();
+        let mut table = CurveTable::new(&saved);
+        let other = Curve::with_width(curves[0].samples().to_vec(), Color::BLACK, 2.);
+        let replace = |curve: &Curve, with: &Curve| Splice {
+            index: 0,
+            removed: vec![curve.clone()],
+            inserted: vec![with.clone()],
+        };
+        let a = table.splice(&replace(&curves[0], &other));
+        let b = table.splice(&replace(&other, &curves[0]));
+        assert_eq!(a.removed, [0]);
+        assert_eq!(a.inserted, [1]);
+        assert_eq!(b.removed, [1]);
+        assert_eq!(b.inserted, [0]);
+        assert_eq!(table.into_curves().len(), 1);
+    }
+}
@@ -0,0 +1,71 @@
+use crate::curve::Curve;
+
+/// A change to a list of curves, replacing `removed` starting at `index` with `inserted`
+#[derive(Debug, Clone)]
+pub struct Splice {
+    pub index: usize,
+    pub removed: Vec<Curve>,
+    pub inserted: Vec<Curve>,
+}
+
+impl Splice {
+    pub fn insert(index: usize, curve: Curve) -> Self {
+        Splice {
+            index,
+            removed: vec![],
+            inserted: vec![curve],
+        }
+    }
+
+    pub fn remove(index: usize, curve: Curve) -> Self {
+        Splice {
+            index,
+            removed: vec![curve],
+            inserted: vec![],
+        }
+    }
+
+    pub fn replace(index: usize, curve: Curve, with: Curve) -> Self {
+        Splice {
+            index,
+            removed: vec![curve],
+            inserted: vec![with],
+        }
+    }
+
+    /// The splice that undoes this one
+    pub fn inverse(&self) -> Self {
+        Splice {
+            index: self.index,
+            removed: self.inserted.clone(),
+            inserted: self.removed.clone(),
+        }
+    }
+
+    /// Number of samples in the curves this holds on to
+    pub fn samples(&self) -> usize {
+        self.removed
+            .iter()
+            .chain(&self.inserted)
+            .map(Curve::len)
+            .sum()
+    }
+
+    /// Where the curve at `index` ends up after the splice, `None` if it's removed.
+    /// Curves replaced one for one, like a moved curve, keep their index.
+    pub fn map_index(&self, index: usize) -> Option<usize> {
+        let end = self.index + self.removed.len();
+        match index {
+            index if index < self.index => Some(index),
+            index if index < end => (self.removed.len() == self.inserted.len()).then_some(index),
+            index => Some(index - self.removed.len() + self.inserted.len()),
+        }
+    }
+
+    pub fn apply(&self, curves: &mut Vec<Curve>) {
+        curves.splice(
+            self.index..self.index + self.removed.len(),
+            self.inserted.iter().cloned(),
+        );
+    }
+}
@@ -0,0 +1,57 @@
+//! Getting drawings out of monstera
+//!
+//! Exports draw the curves through [`Curve::paint`], the same paths the canvas fills and
+//! strokes, in world coordinates.
+use iced::{Color, Rectangle};
+
+use crate::curve::Curve;
+use crate::geometry as geo;
+
+pub mod pdf;
+pub mod png;
+pub mod svg;
+
+/// Space left around the curves when exporting everything, in world units
+const MARGIN: f32 = 8.;
+
+/// The curves to export and the area they're framed in: everything drawn within
+/// `region`, or all curves with a margin around them. `None` if there's nothing to export.
+pub fn framed(curves: &[Curve], region: Option<Rectangle>) -> Option<(Vec<&Curve>, Rectangle)> {
+    let frame = match region {
+        Some(region) => region,
+        None => geo::expand(bounds(curves)?, MARGIN),
+    };
+    let curves: Vec<&Curve> = curves
+        .iter()
+        .filter(|curve| {
+            curve
+                .bounds()
+                .is_some_and(|bounds| geo::overlaps(&bounds, &frame))
+        })
+        .collect();
+    (frame.width > 0. && frame.height > 0.).then_some((curves, frame))
+}
+
+/// Bounds of everything drawn
+pub fn bounds(curves: &[Curve]) -> Option<Rectangle> {
+    curves
+        .iter()
+        .filter_map(Curve::bounds)
+        .reduce(|a, b| a.union(&b))
+}
+
+/// The canvas background `frag.wgsl` draws, a gradient from the first color at the top
+/// left of the screen to the second at the bottom right
+pub fn background() -> (Color, Color) {
+    (
+        Color::from_linear_rgba(0.02, 0.04, 0.04, 1.0),
+        Color::from_linear_rgba(0.015, 0.025, 0.025, 1.0),
+    )
+}
+
+/// 8 bit sRGB channels of a color. iced keeps colors gamma encoded, the palette's
+/// `Color::from_linear_rgba` converts to sRGB when the color is made, so the channels
+/// only need quantizing to match what the screen shows.
+pub fn srgb8(color: Color) -> [u8; 4] {
+    color.into_rgba8()
+}
@@ -0,0 +1,339 @@
+//! PDF export
+//!
+//! Curves stay vector paths. Either a single page is sized to the drawing, or the
+//! drawing is tiled over as many pages of a paper size as it needs, at the same physical
+//! size as on screen.
+use std::fmt;
+
+use iced::widget::canvas::path::lyon_path::Event;
+use iced::widget::canvas::Path;
+use iced::{Color, Rectangle};
+use pdf_writer::types::{FunctionShadingType, LineCapStyle, LineJoinStyle};
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref};
+
+use super::{framed, srgb8};
+use crate::curve::{Curve, Paint};
+use crate::geometry as geo;
+
+pub const EXTENSION: &str = "pdf";
+
+/// Points per world unit, world units are logical pixels at 96 per inch
+const POINTS_PER_UNIT: f32 = 0.75;
+/// Blank border of pages of a paper size, in points
+const PAGE_MARGIN: f32 = 36.;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Paper {
+    A4,
+    Letter,
+}
+
+impl Paper {
+    /// Width and height in points
+    pub fn size(&self) -> (f32, f32) {
+        match self {
+            Paper::A4 => (595.28, 841.89),
+            Paper::Letter => (612., 792.),
+        }
+    }
+}
+
+/// How the drawing is laid out on pages
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Layout {
+    /// A single page fitted to the drawing
+    #[default]
+    Fit,
+    /// As many pages of the paper size as the drawing needs
+    Pages(Paper),
+}
+
+impl Layout {
+    pub const ALL: [Layout; 3] = [
+        Layout::Fit,
+        Layout::Pages(Paper::A4),
+        Layout::Pages(Paper::Letter),
+    ];
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Layout::Fit => "Fit to drawing",
+            Layout::Pages(Paper::A4) => "A4 pages",
+            Layout::Pages(Paper::Letter) => "Letter pages",
+        })
+    }
+}
+
+/// A page showing `area` of the canvas, drawn `margin` points in from its top left
+struct Page {
+    size: (f32, f32),
+    area: Rectangle,
+    margin: f32,
+}
+
+/// A PDF of the curves within `region`, or of all curves, optionally on the canvas
+/// background. `None` if there's nothing to export.
+pub fn export(
+    curves: &[Curve],
+    region: Option<Rectangle>,
+    layout: Layout,
+    background: bool,
+) -> Option<Vec<u8>> {
+    let (curves, frame) = framed(curves, region)?;
+    let pages = pages(&curves, frame, layout);
+    if pages.is_empty() {
+        return None;
+    }
+
+    let mut pdf = Pdf::new();
+    let mut next = Ref::new(1);
+    let mut allocate = || next.bump();
+    let catalog = allocate();
+    let tree = allocate();
+    let function = allocate();
+    let shading = allocate();
+
+    // one graphics state for each level of transparency used
+    let mut alphas: Vec<u8> = curves
+        .iter()
+        .map(|curve| srgb8(curve.color())[3])
+        .filter(|&alpha| alpha < 255)
+        .collect();
+    alphas.sort_unstable();
+    alphas.dedup();
+    let states: Vec<(u8, Ref)> = alphas.into_iter().map(|a| (a, allocate())).collect();
+    for &(alpha, id) in &states {
+        let alpha = alpha as f32 / 255.;
+        pdf.ext_graphics(id)
+            .non_stroking_alpha(alpha)
+            .stroking_alpha(alpha);
+    }
+
+    let ids: Vec<(Ref, Ref)> = pages.iter().map(|_| (allocate(), allocate())).collect();
+    pdf.catalog(catalog).pages(tree);
+    pdf.pages(tree)
+        .kids(ids.iter().map(|(page, _)| *page))
+        .count(ids.len() as i32);
+
+    for (page, &(id, contents)) in pages.iter().zip(&ids) {
+        let (width, height) = page.size;
+        let mut writer = pdf.page(id);
+        writer
+            .parent(tree)
+            .media_box(Rect::new(0., 0., width, height))
+            .contents(contents);
+        let mut resources = writer.resources();
+        if background {
+            let mut shading_writer = resources.shadings();
+            shading_writer.pair(Name(b"Bg"), shading);
+            shading_writer.finish();
+        }
+        let mut state_writer = resources.ext_g_states();
+        for (alpha, id) in &states {
+            state_writer.pair(Name(alpha_name(*alpha).as_bytes()), *id);
+        }
+        state_writer.finish();
+        resources.finish();
+        writer.finish();
+
+        let content = page_content(page, &curves, background);
+        pdf.stream(contents, &content);
+    }
+
+    if background {
+        let (top_left, bottom_right) = super::background();
+        let rgb = |color: Color| {
+            let [r, g, b, _] = srgb8(color);
+            [r, g, b].map(|c| c as f32 / 255.)
+        };
+        pdf.exponential_function(function)
+            .domain([0., 1.])
+            .c0(rgb(top_left))
+            .c1(rgb(bottom_right))
+            .n(1.);
+
+        // the shader mixes by the mean of the normalized screen coordinates, so the
+        // gradient runs from the top left along (1 / width, -1 / height) of each page
+        let (width, height) = pages[0].size;
+        let direction = (1. / (2. * width), -1. / (2. * height));
+        let length_squared = direction.0 * direction.0 + direction.1 * direction.1;
+        let mut shading_writer = pdf.function_shading(shading);
+        shading_writer
+            .shading_type(FunctionShadingType::Axial)
+            .function(function)
+            .coords([
+                0.,
+                height,
+                direction.0 / length_squared,
+                height + direction.1 / length_squared,
+            ])
+            .extend([true, true]);
+        shading_writer.color_space().device_rgb();
+    }
+
+    Some(pdf.finish())
+}
+
+/// Pages covering `frame`, leaving out pages of a paper size that would be empty
+fn pages(curves: &[&Curve], frame: Rectangle, layout: Layout) -> Vec<Page> {
+    let paper = match layout {
+        Layout::Fit => {
+            return vec![Page {
+                size: (
+                    frame.width * POINTS_PER_UNIT,
+                    frame.height * POINTS_PER_UNIT,
+                ),
+                area: frame,
+                margin: 0.,
+            }]
+        }
+        Layout::Pages(paper) => paper.size(),
+    };
+    let tile_width = (paper.0 - 2. * PAGE_MARGIN) / POINTS_PER_UNIT;
+    let tile_height = (paper.1 - 2. * PAGE_MARGIN) / POINTS_PER_UNIT;
+    let columns = (frame.width / tile_width).ceil().max(1.) as usize;
+    let rows = (frame.height / tile_height).ceil().max(1.) as usize;
+
+    // in reading order, a row of pages at a time
+    (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (row, column)))
+        .map(|(row, column)| Rectangle {
+            x: frame.x + column as f32 * tile_width,
+            y: frame.y + row as f32 * tile_height,
+            width: tile_width,
+            height: tile_height,
+        })
+        .filter(|area| {
+            curves
+                .iter()
+                .filter_map(|curve| curve.bounds())
+                .any(|bounds| geo::overlaps(&bounds, area))
+        })
+        .map(|area| Page {
+            size: paper,
+            area,
+            margin: PAGE_MARGIN,
+        })
+        .collect()
+}
+
+fn page_content(page: &Page, curves: &[&Curve], background: bool) -> Vec<u8> {
+    let (width, height) = page.size;
+    let mut content = Content::new();
+    if background {
+        content.save_state();
+        content
+            .rect(0., 0., width, height)
+            .clip_nonzero()
+            .end_path();
+        content.shading(Name(b"Bg"));
+        content.restore_state();
+    }
+
+    content.save_state();
+    let scale = POINTS_PER_UNIT;
+    content
+        .rect(
+            page.margin,
+            page.margin,
+            page.area.width * scale,
+            page.area.height * scale,
+        )
+        .clip_nonzero()
+        .end_path();
+    // world coordinates point down, page coordinates up
+    content.transform([
+        scale,
+        0.,
+        0.,
+        -scale,
+        page.margin - page.area.x * scale,
+        height - page.margin + page.area.y * scale,
+    ]);
+    content
+        .set_line_cap(LineCapStyle::RoundCap)
+        .set_line_join(LineJoinStyle::RoundJoin);
+
+    for curve in curves {
+        if !curve
+            .bounds()
+            .is_some_and(|bounds| geo::overlaps(&bounds, &page.area))
+        {
+            continue;
+        }
+        let [r, g, b, a] = srgb8(curve.color());
+        let [r, g, b] = [r, g, b].map(|c| c as f32 / 255.);
+        content.save_state();
+        if a < 255 {
+            content.set_parameters(Name(alpha_name(a).as_bytes()));
+        }
+        match curve.paint() {
+            Paint::Fill(paths) => {
+                content.set_fill_rgb(r, g, b);
+                for path in paths {
+                    if append(&mut content, path) {
+                        content.fill_nonzero();
+                    }
+                }
+            }
+            Paint::Stroke { path, width } => {
+                content.set_stroke_rgb(r, g, b).set_line_width(width);
+                if append(&mut content, path) {
+                    content.stroke();
+                }
+            }
+        }
+        content.restore_state();
+    }
+    content.restore_state();
+    content.finish()
+}
+
+/// Add the segments of `path` to the content, returning whether there were any
+fn append(content: &mut Content, path: &Path) -> bool {
+    let mut any = false;
+    let mut current = (0., 0.);
+    for event in path.raw().iter() {
+        any = true;
+        match event {
+            Event::Begin { at } => {
+                content.move_to(at.x, at.y);
+                current = (at.x, at.y);
+            }
+            Event::Line { to, .. } => {
+                content.line_to(to.x, to.y);
+                current = (to.x, to.y);
+            }
+            Event::Quadratic { ctrl, to, .. } => {
+                // PDF only has cubics, raise the degree
+                let (x, y) = current;
+                content.cubic_to(
+                    x + (ctrl.x - x) * 2. / 3.,
+                    y + (ctrl.y - y) * 2. / 3.,
+                    to.x + (ctrl.x - to.x) * 2. / 3.,
+                    to.y + (ctrl.y - to.y) * 2. / 3.,
+                    to.x,
+                    to.y,
+                );
+                current = (to.x, to.y);
+            }
+            Event::Cubic {
+                ctrl1, ctrl2, to, ..
+            } => {
+                content.cubic_to(ctrl1.x, ctrl1.y, ctrl2.x, ctrl2.y, to.x, to.y);
+                current = (to.x, to.y);
+            }
+            Event::End { close: true, .. } => {
+                content.close_path();
+            }
+            Event::End { close: false, .. } => {}
+        }
+    }
+    any
+}
+
+fn alpha_name(alpha: u8) -> String {
+    format!("A{alpha}")
+}
@@ -0,0 +1,161 @@
+//! PNG export
+//!
+//! Rendered on the CPU, so it works without a GPU or a window.
+use std::fmt;
+
+use iced::widget::canvas::path::lyon_path::Event;
+use iced::widget::canvas::Path;
+use iced::{Color, Rectangle};
+use tiny_skia::{
+    FillRule, LineCap, LineJoin, Paint as SkiaPaint, PathBuilder, Pixmap, Stroke, Transform,
+};
+
+use super::{framed, srgb8};
+use crate::curve::{Curve, Paint};
+
+pub const EXTENSION: &str = "png";
+
+/// Largest width or height of an image, in pixels
+const MAX_SIDE: u32 = 16384;
+
+#[derive(Debug)]
+pub enum PngError {
+    /// There are no curves, or none in the region
+    Empty,
+    TooLarge {
+        width: u32,
+        height: u32,
+    },
+    Encode(String),
+}
+
+impl fmt::Display for PngError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PngError::Empty => write!(f, "nothing to export"),
+            PngError::TooLarge { width, height } => write!(
+                f,
+                "the image would be {width}×{height} pixels, at most {MAX_SIDE} are \
+                supported on each side, try a smaller scale or region"
+            ),
+            PngError::Encode(error) => write!(f, "could not encode the image: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for PngError {}
+
+/// Render the curves within `region`, or all curves, at `scale` pixels per world unit.
+/// Without the canvas `background` the image is transparent where nothing is drawn.
+pub fn render(
+    curves: &[Curve],
+    region: Option<Rectangle>,
+    scale: f32,
+    background: bool,
+) -> Result<Pixmap, PngError> {
+    let (curves, frame) = framed(curves, region).ok_or(PngError::Empty)?;
+    let width = (frame.width * scale).ceil() as u32;
+    let height = (frame.height * scale).ceil() as u32;
+    if width > MAX_SIDE || height > MAX_SIDE {
+        return Err(PngError::TooLarge { width, height });
+    }
+    let mut pixmap = Pixmap::new(width.max(1), height.max(1)).ok_or(PngError::Empty)?;
+    if background {
+        fill_background(&mut pixmap);
+    }
+
+    let transform = Transform::from_translate(-frame.x, -frame.y).post_scale(scale, scale);
+    for curve in curves {
+        let mut paint = SkiaPaint::default();
+        paint.set_color(color(curve.color()));
+        paint.anti_alias = true;
+        match curve.paint() {
+            Paint::Fill(paths) => {
+                for path in paths.into_iter().filter_map(skia_path) {
+                    pixmap.fill_path(&path, &paint, FillRule::Winding, transform, None);
+                }
+            }
+            Paint::Stroke { path, width } => {
+                let stroke = Stroke {
+                    width,
+                    line_cap: LineCap::Round,
+                    line_join: LineJoin::Round,
+                    ..Stroke::default()
+                };
+                if let Some(path) = skia_path(path) {
+                    pixmap.stroke_path(&path, &paint, &stroke, transform, None);
+                }
+            }
+        }
+    }
+    Ok(pixmap)
+}
+
+/// Render like [`render`] and encode as PNG
+pub fn export(
+    curves: &[Curve],
+    region: Option<Rectangle>,
+    scale: f32,
+    background: bool,
+) -> Result<Vec<u8>, PngError> {
+    render(curves, region, scale, background)?
+        .encode_png()
+        .map_err(|error| PngError::Encode(error.to_string()))
+}
+
+/// The gradient of the canvas, stretched over the image like over the screen
+fn fill_background(pixmap: &mut Pixmap) {
+    let (top_left, bottom_right) = super::background();
+    let (width, height) = (pixmap.width() as f32, pixmap.height() as f32);
+    // the shader mixes by the mean of the normalized coordinates, so the gradient
+    // runs along (1 / width, 1 / height) and ends where that mean is 1
+    let direction = (1. / (2. * width), 1. / (2. * height));
+    let length_squared = direction.0 * direction.0 + direction.1 * direction.1;
+    let end = tiny_skia::Point::from_xy(direction.0 / length_squared, direction.1 / length_squared);
+    let shader = tiny_skia::LinearGradient::new(
+        tiny_skia::Point::from_xy(0., 0.),
+        end,
+        vec![
+            tiny_skia::GradientStop::new(0., color(top_left)),
+            tiny_skia::GradientStop::new(1., color(bottom_right)),
+        ],
+        tiny_skia::SpreadMode::Pad,
+        Transform::identity(),
+    );
+    match shader {
+        Some(shader) => {
+            let paint = SkiaPaint {
+                shader,
+                ..SkiaPaint::default()
+            };
+            let rect = tiny_skia::Rect::from_xywh(0., 0., width, height);
+            if let Some(rect) = rect {
+                pixmap.fill_rect(rect, &paint, Transform::identity(), None);
+            }
+        }
+        None => pixmap.fill(color(top_left)),
+    }
+}
+
+fn color(color: Color) -> tiny_skia::Color {
+    let [r, g, b, a] = srgb8(color);
+    tiny_skia::Color::from_rgba8(r, g, b, a)
+}
+
+/// The same path for tiny-skia, `None` if it's empty
+fn skia_path(path: &Path) -> Option<tiny_skia::Path> {
+    let mut builder = PathBuilder::new();
+    for event in path.raw().iter() {
+        match event {
+            Event::Begin { at } => builder.move_to(at.x, at.y),
+            Event::Line { to, .. } => builder.line_to(to.x, to.y),
+            Event::Quadratic { ctrl, to, .. } => builder.quad_to(ctrl.x, ctrl.y, to.x, to.y),
+            Event::Cubic {
+                ctrl1, ctrl2, to, ..
+            } => builder.cubic_to(ctrl1.x, ctrl1.y, ctrl2.x, ctrl2.y, to.x, to.y),
+            Event::End { close: true, .. } => builder.close(),
+            Event::End { close: false, .. } => {}
+        }
+    }
+    builder.finish()
+}
@@ -0,0 +1,128 @@
+//! SVG export
+//!
+//! Each curve becomes a `<g>` in stroke order, holding one `<path>` per outline the canvas
+//! fills, or a single stroked `<path>` for shapes. Groups of outlines carry the
+//! [`STROKE_CLASS`] class so an import can tell them from other filled shapes.
+//! Coordinates are world units, framed by the `viewBox`.
+use std::fmt::Write;
+
+use iced::widget::canvas::path::lyon_path::Event;
+use iced::widget::canvas::Path;
+use iced::Rectangle;
+
+use super::{framed, srgb8};
+use crate::curve::{Curve, Paint};
+
+pub const EXTENSION: &str = "svg";
+/// Class of the groups holding the outlines of a stroke
+pub const STROKE_CLASS: &str = "monstera-stroke";
+
+/// An SVG document of the curves within `region`, or of all curves. `None` if there's
+/// nothing to export.
+pub fn export(curves: &[Curve], region: Option<Rectangle>) -> Option<String> {
+    document(curves, region, "")
+}
+
+/// An SVG document of all curves with `metadata` first inside the root element, e.g.
+/// data for monstera to read back. `None` if there's nothing to export.
+pub fn export_with_metadata(curves: &[Curve], metadata: &str) -> Option<String> {
+    document(curves, None, metadata)
+}
+
+fn document(curves: &[Curve], region: Option<Rectangle>, metadata: &str) -> Option<String> {
+    let (curves, frame) = framed(curves, region)?;
+    let mut svg = String::new();
+    let _ = writeln!(svg, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{}" height="{}">"#,
+        number(frame.x),
+        number(frame.y),
+        number(frame.width),
+        number(frame.height),
+        number(frame.width),
+        number(frame.height),
+    );
+    if !metadata.is_empty() {
+        let _ = writeln!(svg, "  {metadata}");
+    }
+    for curve in curves {
+        svg.push_str(&group(curve));
+    }
+    svg.push_str("</svg>\n");
+    Some(svg)
+}
+
+fn group(curve: &Curve) -> String {
+    let [r, g, b, a] = srgb8(curve.color());
+    let color = format!("#{r:02x}{g:02x}{b:02x}");
+    let opacity = |property: &str| match a {
+        255 => String::new(),
+        a => format!(r#" {property}-opacity="{}""#, number(a as f32 / 255.)),
+    };
+    match curve.paint() {
+        Paint::Fill(paths) => {
+            let mut group = format!(
+                r#"  <g class="{STROKE_CLASS}" fill="{color}"{}>"#,
+                opacity("fill")
+            );
+            group.push('\n');
+            for path in paths {
+                let _ = writeln!(group, r#"    <path d="{}"/>"#, data(path));
+            }
+            group.push_str("  </g>\n");
+            group
+        }
+        Paint::Stroke { path, width } => format!(
+            "  <g fill=\"none\" stroke=\"{color}\"{} stroke-width=\"{}\" \
+            stroke-linecap=\"round\" stroke-linejoin=\"round\">\n    <path d=\"{}\"/>\n  </g>\n",
+            opacity("stroke"),
+            number(width),
+            data(path),
+        ),
+    }
+}
+
+/// Path data of an iced path
+pub fn data(path: &Path) -> String {
+    let mut data = String::new();
+    for event in path.raw().iter() {
+        let _ = match event {
+            Event::Begin { at } => write!(data, "M{} {}", number(at.x), number(at.y)),
+            Event::Line { to, .. } => write!(data, "L{} {}", number(to.x), number(to.y)),
+            Event::Quadratic { ctrl, to, .. } => write!(
+                data,
+                "Q{} {} {} {}",
+                number(ctrl.x),
+                number(ctrl.y),
+                number(to.x),
+                number(to.y)
+            ),
+            Event::Cubic {
+                ctrl1, ctrl2, to, ..
+            } => write!(
+                data,
+                "C{} {} {} {} {} {}",
+                number(ctrl1.x),
+                number(ctrl1.y),
+                number(ctrl2.x),
+                number(ctrl2.y),
+                number(to.x),
+                number(to.y)
+            ),
+            Event::End { close: true, .. } => write!(data, "Z"),
+            Event::End { close: false, .. } => Ok(()),
+        };
+    }
+    data
+}
+
+/// A coordinate rounded to a hundredth of a world unit, without trailing zeros
+fn number(value: f32) -> String {
+    let value = format!("{value:.2}");
+    let value = value.trim_end_matches('0').trim_end_matches('.');
+    match value {
+        "-0" => "0".into(),
+        value => value.into(),
+    }
+}
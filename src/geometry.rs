@@ -0,0 +1,369 @@
+//! Geometry queries on polylines
+//!
+//! Orientation tests are done in `f64`, strokes can be far from the origin on the
+//! infinite canvas where `f32` cross products lose the sign of nearly parallel segments.
+use std::ops::Range;
+
+use iced::{Point, Rectangle, Size};
+
+/// Number of segments covered by each box of a [`BoundsIndex`]
+const RUN_LEN: usize = 64;
+
+/// Bounding boxes over consecutive runs of a polyline, so queries on long
+/// curves can skip most of their segments
+#[derive(Debug, Clone, Default)]
+pub struct BoundsIndex {
+    runs: Vec<Rectangle>,
+    total: Option<Rectangle>,
+}
+
+impl BoundsIndex {
+    pub fn new(points: impl IntoIterator<Item = Point>) -> Self {
+        let mut index = BoundsIndex::default();
+        points
+            .into_iter()
+            .enumerate()
+            .for_each(|(i, point)| index.push(i, point));
+        index
+    }
+
+    /// Account for `point`, appended to the polyline at `index`
+    pub fn push(&mut self, index: usize, point: Point) {
+        let run = index / RUN_LEN;
+        // the first point of a run also ends the previous one
+        if run > 0 && index == run * RUN_LEN {
+            self.runs[run - 1] = include(self.runs[run - 1], point);
+        }
+        match self.runs.get_mut(run) {
+            Some(bounds) => *bounds = include(*bounds, point),
+            None => self.runs.push(Rectangle::new(point, Size::ZERO)),
+        }
+        self.total = Some(match self.total {
+            Some(total) => include(total, point),
+            None => Rectangle::new(point, Size::ZERO),
+        });
+    }
+
+    pub fn total(&self) -> Option<Rectangle> {
+        self.total
+    }
+
+    /// Bounds of each run, with the range of points in it. Ranges overlap by one
+    /// point so every segment of a polyline with `len` points is in a run.
+    pub fn runs(&self, len: usize) -> impl Iterator<Item = (&Rectangle, Range<usize>)> {
+        self.runs
+            .iter()
+            .enumerate()
+            .map(move |(run, bounds)| (bounds, run * RUN_LEN..((run + 1) * RUN_LEN + 1).min(len)))
+    }
+}
+
+fn include(rect: Rectangle, point: Point) -> Rectangle {
+    let x = rect.x.min(point.x);
+    let y = rect.y.min(point.y);
+    Rectangle {
+        x,
+        y,
+        width: (rect.x + rect.width).max(point.x) - x,
+        height: (rect.y + rect.height).max(point.y) - y,
+    }
+}
+
+/// Grow `rect` by `by` on every side
+pub fn expand(rect: Rectangle, by: f32) -> Rectangle {
+    Rectangle {
+        x: rect.x - by,
+        y: rect.y - by,
+        width: rect.width + by * 2.,
+        height: rect.height + by * 2.,
+    }
+}
+
+/// Whether two rectangles touch, unlike [`Rectangle::intersects`] this holds for
+/// rectangles with no area, like the bounds of a straight horizontal stroke
+pub fn overlaps(a: &Rectangle, b: &Rectangle) -> bool {
+    a.x <= b.x + b.width && b.x <= a.x + a.width && a.y <= b.y + b.height && b.y <= a.y + a.height
+}
+
+/// Like [`Rectangle::contains`], but including the right and bottom edges
+pub fn contains(rect: &Rectangle, point: Point) -> bool {
+    rect.x <= point.x
+        && point.x <= rect.x + rect.width
+        && rect.y <= point.y
+        && point.y <= rect.y + rect.height
+}
+
+/// Distance from `point` to the closest point of `rect`, zero inside
+pub fn rectangle_distance(rect: &Rectangle, point: Point) -> f32 {
+    let dx = (rect.x - point.x)
+        .max(point.x - (rect.x + rect.width))
+        .max(0.);
+    let dy = (rect.y - point.y)
+        .max(point.y - (rect.y + rect.height))
+        .max(0.);
+    dx.hypot(dy)
+}
+
+pub fn segment_distance(point: Point, a: Point, b: Point) -> f32 {
+    let ab = b - a;
+    let len_squared = ab.x * ab.x + ab.y * ab.y;
+    if len_squared == 0.0 {
+        return point.distance(a);
+    }
+    let ap = point - a;
+    let t = ((ap.x * ab.x + ap.y * ab.y) / len_squared).clamp(0.0, 1.0);
+    point.distance(a + ab * t)
+}
+
+/// Sign of the turn from `a` to `b` to `c`
+fn orientation(a: Point, b: Point, c: Point) -> f64 {
+    let (ax, ay) = (a.x as f64, a.y as f64);
+    (b.x as f64 - ax) * (c.y as f64 - ay) - (b.y as f64 - ay) * (c.x as f64 - ax)
+}
+
+/// Whether `p`, known to be collinear with `a`..`b`, lies within the segment
+fn on_segment(p: Point, a: Point, b: Point) -> bool {
+    p.x >= a.x.min(b.x) && p.x <= a.x.max(b.x) && p.y >= a.y.min(b.y) && p.y <= a.y.max(b.y)
+}
+
+/// Whether segments `a`..`b` and `c`..`d` touch, including at their end points
+pub fn segments_intersect(a: Point, b: Point, c: Point, d: Point) -> bool {
+    let (d1, d2) = (orientation(c, d, a), orientation(c, d, b));
+    let (d3, d4) = (orientation(a, b, c), orientation(a, b, d));
+
+    if ((d1 > 0. && d2 < 0.) || (d1 < 0. && d2 > 0.))
+        && ((d3 > 0. && d4 < 0.) || (d3 < 0. && d4 > 0.))
+    {
+        return true;
+    }
+    (d1 == 0. && on_segment(a, c, d))
+        || (d2 == 0. && on_segment(b, c, d))
+        || (d3 == 0. && on_segment(c, a, b))
+        || (d4 == 0. && on_segment(d, a, b))
+}
+
+/// Shortest distance between segments `a`..`b` and `c`..`d`
+pub fn segments_distance(a: Point, b: Point, c: Point, d: Point) -> f32 {
+    if segments_intersect(a, b, c, d) {
+        return 0.;
+    }
+    segment_distance(a, c, d)
+        .min(segment_distance(b, c, d))
+        .min(segment_distance(c, a, b))
+        .min(segment_distance(d, a, b))
+}
+
+pub fn segment_intersects_rectangle(a: Point, b: Point, rect: &Rectangle) -> bool {
+    if contains(rect, a) || contains(rect, b) {
+        return true;
+    }
+    let corners = [
+        Point::new(rect.x, rect.y),
+        Point::new(rect.x + rect.width, rect.y),
+        Point::new(rect.x + rect.width, rect.y + rect.height),
+        Point::new(rect.x, rect.y + rect.height),
+    ];
+    (0..4).any(|i| segments_intersect(a, b, corners[i], corners[(i + 1) % 4]))
+}
+
+/// Even-odd test, the polygon is closed implicitly
+pub fn point_in_polygon(point: Point, polygon: &[Point]) -> bool {
+    let (px, py) = (point.x as f64, point.y as f64);
+    let mut inside = false;
+    let mut previous = match polygon.last() {
+        Some(last) => *last,
+        None => return false,
+    };
+    for &current in polygon {
+        let (x1, y1) = (previous.x as f64, previous.y as f64);
+        let (x2, y2) = (current.x as f64, current.y as f64);
+        if (y2 > py) != (y1 > py) && px < (x1 - x2) * (py - y2) / (y1 - y2) + x2 {
+            inside = !inside;
+        }
+        previous = current;
+    }
+    inside
+}
+
+/// Whether the segment `a`..`b` crosses the edge of the closed `polygon`
+pub fn segment_crosses_polygon(a: Point, b: Point, polygon: &[Point]) -> bool {
+    (0..polygon.len())
+        .any(|i| segments_intersect(a, b, polygon[i], polygon[(i + 1) % polygon.len()]))
+}
+
+/// Bounds of a closed polygon
+pub fn polygon_bounds(polygon: &[Point]) -> Option<Rectangle> {
+    BoundsIndex::new(polygon.iter().copied()).total()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p(x: f32, y: f32) -> Point {
+        Point::new(x, y)
+    }
+
+    const SQUARE: [Point; 4] = [
+        Point::new(0., 0.),
+        Point::new(10., 0.),
+        Point::new(10., 10.),
+        Point::new(0., 10.),
+    ];
+
+    #[test]
+    fn segments_crossing_touching_and_apart() {
+        assert!(segments_intersect(
+            p(0., 0.),
+            p(10., 10.),
+            p(0., 10.),
+            p(10., 0.)
+        ));
+        // touching at an end
+        assert!(segments_intersect(
+            p(0., 0.),
+            p(5., 5.),
+            p(5., 5.),
+            p(10., 0.)
+        ));
+        // collinear and overlapping
+        assert!(segments_intersect(
+            p(0., 0.),
+            p(6., 0.),
+            p(4., 0.),
+            p(10., 0.)
+        ));
+        // collinear with a gap
+        assert!(!segments_intersect(
+            p(0., 0.),
+            p(4., 0.),
+            p(6., 0.),
+            p(10., 0.)
+        ));
+        // parallel
+        assert!(!segments_intersect(
+            p(0., 0.),
+            p(10., 0.),
+            p(0., 1.),
+            p(10., 1.)
+        ));
+    }
+
+    #[test]
+    fn segments_far_from_the_origin() {
+        let (x, y) = (1.0e5, -1.0e5);
+        assert!(segments_intersect(
+            p(x, y),
+            p(x + 10., y + 10.),
+            p(x, y + 10.),
+            p(x + 10., y)
+        ));
+        assert!(!segments_intersect(
+            p(x, y),
+            p(x + 10., y),
+            p(x, y + 1.),
+            p(x + 10., y + 1.)
+        ));
+    }
+
+    #[test]
+    fn distances() {
+        assert_eq!(segment_distance(p(5., 3.), p(0., 0.), p(10., 0.)), 3.);
+        // past the end, to the end point
+        assert_eq!(segment_distance(p(13., 4.), p(0., 0.), p(10., 0.)), 5.);
+        assert_eq!(segment_distance(p(3., 4.), p(0., 0.), p(0., 0.)), 5.);
+        assert_eq!(
+            segments_distance(p(0., 0.), p(10., 0.), p(0., 2.), p(10., 2.)),
+            2.
+        );
+        assert_eq!(
+            segments_distance(p(0., 0.), p(10., 10.), p(0., 10.), p(10., 0.)),
+            0.
+        );
+        let rect = Rectangle::new(p(0., 0.), Size::new(10., 10.));
+        assert_eq!(rectangle_distance(&rect, p(5., 5.)), 0.);
+        assert_eq!(rectangle_distance(&rect, p(13., 14.)), 5.);
+    }
+
+    #[test]
+    fn segment_and_rectangle() {
+        let rect = Rectangle::new(p(0., 0.), Size::new(10., 10.));
+        assert!(segment_intersects_rectangle(p(2., 2.), p(3., 3.), &rect));
+        // through it with both ends outside
+        assert!(segment_intersects_rectangle(p(-5., 5.), p(15., 5.), &rect));
+        // on its right edge
+        assert!(segment_intersects_rectangle(
+            p(10., -5.),
+            p(10., 15.),
+            &rect
+        ));
+        assert!(!segment_intersects_rectangle(
+            p(-5., -5.),
+            p(-1., 15.),
+            &rect
+        ));
+    }
+
+    #[test]
+    fn rectangles_without_area_overlap() {
+        let line = Rectangle::new(p(0., 5.), Size::new(10., 0.));
+        let other = Rectangle::new(p(5., 0.), Size::new(0., 10.));
+        assert!(overlaps(&line, &other));
+        assert!(!overlaps(
+            &line,
+            &Rectangle::new(p(11., 0.), Size::new(1., 10.))
+        ));
+    }
+
+    #[test]
+    fn polygons() {
+        assert!(point_in_polygon(p(5., 5.), &SQUARE));
+        assert!(!point_in_polygon(p(15., 5.), &SQUARE));
+        assert!(!point_in_polygon(p(5., 5.), &[]));
+        // the notch of a concave polygon is outside
+        let notched = [p(0., 0.), p(10., 0.), p(10., 10.), p(5., 2.), p(0., 10.)];
+        assert!(!point_in_polygon(p(5., 8.), &notched));
+        assert!(point_in_polygon(p(2., 2.), &notched));
+
+        assert!(segment_crosses_polygon(p(5., 5.), p(15., 5.), &SQUARE));
+        assert!(!segment_crosses_polygon(p(2., 2.), p(8., 8.), &SQUARE));
+        assert_eq!(
+            polygon_bounds(&SQUARE),
+            Some(Rectangle::new(p(0., 0.), Size::new(10., 10.)))
+        );
+    }
+
+    #[test]
+    fn bounds_index_covers_every_segment() {
+        let points: Vec<Point> = (0..200).map(|i| p(i as f32, (i % 7) as f32)).collect();
+        let index = BoundsIndex::new(points.iter().copied());
+        assert_eq!(
+            index.total(),
+            Some(Rectangle::new(p(0., 0.), Size::new(199., 6.)))
+        );
+
+        let runs: Vec<_> = index.runs(points.len()).collect();
+        assert_eq!(runs.len(), points.len().div_ceil(RUN_LEN));
+        for i in 0..points.len() - 1 {
+            let (bounds, _) = runs
+                .iter()
+                .find(|(_, range)| range.contains(&i) && range.contains(&(i + 1)))
+                .expect("a run with the segment");
+            assert!(contains(bounds, points[i]) && contains(bounds, points[i + 1]));
+        }
+    }
+
+    #[test]
+    fn bounds_index_grows_with_pushes() {
+        let mut index = BoundsIndex::default();
+        assert_eq!(index.total(), None);
+        for i in 0..=RUN_LEN {
+            index.push(i, p(i as f32, 0.));
+        }
+        // the first point of the second run also ends the first
+        let runs: Vec<_> = index.runs(RUN_LEN + 1).collect();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].0.width, RUN_LEN as f32);
+        assert_eq!(runs[0].1, 0..RUN_LEN + 1);
+    }
+}
@@ -0,0 +1,588 @@
+//! Undo and redo
+//!
+//! Edits are kept as a tree, so drawing after undoing starts a new branch instead of
+//! throwing the undone steps away. Any node can be jumped to by undoing up to where
+//! the branches meet and redoing down to it.
+use crate::curve::Curve;
+use crate::document::{CurveData, CurveTable, HistoryData, NodeData};
+use crate::edit::Splice;
+use crate::layer::{LayerId, Removal};
+
+/// Most steps that can be undone
+const MAX_STEPS: usize = 500;
+/// Most samples the curves of all steps may hold together, so long sessions with big
+/// edits stay bounded. The oldest steps are forgotten first.
+const MAX_SAMPLES: usize = 1_000_000;
+
+/// Splices that are undone and redone together
+pub type Step = Vec<Splice>;
+
+#[derive(Debug, Default)]
+struct Node {
+    /// `None` for the root, the oldest state remembered
+    parent: Option<usize>,
+    /// What leads here from the parent
+    step: Step,
+    /// Layer deleted along with the step, its curves are removed by the step
+    removal: Option<Removal>,
+    /// Child redo goes to, the one last left by undoing
+    next: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct History {
+    /// In the order they were added, parents before their children
+    nodes: Vec<Node>,
+    /// Node the curves are at
+    current: usize,
+    /// Step being gathered, e.g. over an eraser drag
+    open: Option<Step>,
+    /// Samples held by the steps of all nodes
+    samples: usize,
+}
+
+/// What undoing, redoing or jumping changes, the splices applied in order and then
+/// the layers changed in order
+#[derive(Debug, Default)]
+pub struct Change {
+    pub splices: Step,
+    pub layers: Vec<LayerChange>,
+}
+
+#[derive(Debug, Clone)]
+pub enum LayerChange {
+    Remove(LayerId),
+    Restore(Removal),
+}
+
+/// A node as listed in the history panel
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub node: usize,
+    /// How many branches off the first one this node is
+    pub depth: usize,
+    pub label: String,
+    pub current: bool,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        History {
+            nodes: vec![Node::default()],
+            current: 0,
+            open: None,
+            samples: 0,
+        }
+    }
+}
+
+impl History {
+    /// Gather the following edits into one step, until [`History::end`]
+    pub fn begin(&mut self) {
+        self.open.get_or_insert_with(Vec::new);
+    }
+
+    pub fn end(&mut self) {
+        if let Some(step) = self.open.take().filter(|step| !step.is_empty()) {
+            self.push(step, None);
+        }
+    }
+
+    /// Remember an edit that was just applied, as a new branch if something was undone
+    pub fn record(&mut self, splices: Step) {
+        match &mut self.open {
+            Some(step) => step.extend(splices),
+            None => self.push(splices, None),
+        }
+    }
+
+    /// Remember deleting a layer with the edit removing its curves, as a step of its own
+    pub fn record_removal(&mut self, splices: Step, removal: Removal) {
+        self.end();
+        self.push(splices, Some(removal));
+    }
+
+    fn push(&mut self, step: Step, removal: Option<Removal>) {
+        self.samples += samples(&step);
+        let node = self.nodes.len();
+        self.nodes.push(Node {
+            parent: Some(self.current),
+            step,
+            removal,
+            next: None,
+        });
+        self.nodes[self.current].next = Some(node);
+        self.current = node;
+        self.trim();
+    }
+
+    /// Forget the oldest steps until the history is within bounds. Branches that end
+    /// somewhere else than the current node are cut back, and the root moves down when
+    /// there's only one way to go from it.
+    fn trim(&mut self) {
+        while self.nodes.len() > MAX_STEPS + 1 || self.samples > MAX_SAMPLES {
+            match self.children(0)[..] {
+                [only] if self.current != 0 && only != self.current => {
+                    // the only child becomes the root
+                    let node = &mut self.nodes[only];
+                    self.samples -= samples(&node.step);
+                    node.step.clear();
+                    node.removal = None;
+                    node.parent = None;
+                    self.remove(0);
+                }
+                _ => {
+                    let Some(oldest) = (1..self.nodes.len())
+                        .find(|&node| node != self.current && self.children(node).is_empty())
+                    else {
+                        // the current step is kept however big it is
+                        break;
+                    };
+                    self.samples -= samples(&self.nodes[oldest].step);
+                    self.remove(oldest);
+                }
+            }
+        }
+    }
+
+    /// Take a node out, it must not be referred to by any other node but its parent
+    fn remove(&mut self, index: usize) {
+        self.nodes.remove(index);
+        let shift = |node: usize| match node > index {
+            true => node - 1,
+            false => node,
+        };
+        for node in &mut self.nodes {
+            node.parent = node.parent.map(shift);
+            node.next = node.next.filter(|&next| next != index).map(shift);
+        }
+        self.current = shift(self.current);
+    }
+
+    fn children(&self, index: usize) -> Vec<usize> {
+        (index + 1..self.nodes.len())
+            .filter(|&node| self.nodes[node].parent == Some(index))
+            .collect()
+    }
+
+    /// What undoes the current step
+    pub fn undo(&mut self) -> Option<Change> {
+        self.end();
+        let parent = self.nodes[self.current].parent?;
+        let mut change = Change::default();
+        self.undo_node(self.current, &mut change);
+        self.nodes[parent].next = Some(self.current);
+        self.current = parent;
+        Some(change)
+    }
+
+    /// What redoes the step last undone from here, or the newest branch
+    pub fn redo(&mut self) -> Option<Change> {
+        self.end();
+        let next = self.next()?;
+        let mut change = Change::default();
+        self.redo_node(next, &mut change);
+        self.current = next;
+        Some(change)
+    }
+
+    fn undo_node(&self, node: usize, change: &mut Change) {
+        let node = &self.nodes[node];
+        change.splices.extend(inverse(&node.step));
+        if let Some(removal) = &node.removal {
+            change.layers.push(LayerChange::Restore(removal.clone()));
+        }
+    }
+
+    fn redo_node(&self, node: usize, change: &mut Change) {
+        let node = &self.nodes[node];
+        change.splices.extend(node.step.iter().cloned());
+        if let Some(removal) = &node.removal {
+            change.layers.push(LayerChange::Remove(removal.layer.id));
+        }
+    }
+
+    fn next(&self) -> Option<usize> {
+        self.nodes[self.current]
+            .next
+            .or_else(|| self.children(self.current).last().copied())
+    }
+
+    /// What takes the curves and layers from the current node to `target`
+    pub fn jump(&mut self, target: usize) -> Option<Change> {
+        self.end();
+        if target >= self.nodes.len() || target == self.current {
+            return None;
+        }
+        let up = self.ancestors(self.current);
+        let down = self.ancestors(target);
+        let common = *up.iter().find(|node| down.contains(node))?;
+
+        let mut change = Change::default();
+        for &node in up.iter().take_while(|&&node| node != common) {
+            self.undo_node(node, &mut change);
+        }
+        let down: Vec<usize> = down
+            .into_iter()
+            .take_while(|&node| node != common)
+            .collect();
+        for &node in down.iter().rev() {
+            self.redo_node(node, &mut change);
+            if let Some(parent) = self.nodes[node].parent {
+                // so redo follows the way taken
+                self.nodes[parent].next = Some(node);
+            }
+        }
+        self.current = target;
+        Some(change)
+    }
+
+    /// `node` and the nodes above it, up to the root
+    fn ancestors(&self, node: usize) -> Vec<usize> {
+        std::iter::successors(Some(node), |&node| self.nodes[node].parent).collect()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.current != 0 || self.open.as_ref().is_some_and(|step| !step.is_empty())
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.next().is_some()
+    }
+
+    /// Every node, each branch listed after the branch it splits off from
+    pub fn entries(&self) -> Vec<Entry> {
+        let mut entries = Vec::with_capacity(self.nodes.len());
+        let mut stack = vec![(0, 0)];
+        while let Some((node, depth)) = stack.pop() {
+            entries.push(Entry {
+                node,
+                depth,
+                label: label(&self.nodes[node]),
+                current: node == self.current,
+            });
+            // the first child continues the branch, later ones start new branches
+            let children = self.children(node);
+            for (i, &child) in children.iter().enumerate().rev() {
+                stack.push((child, depth + usize::from(i > 0)));
+            }
+        }
+        entries
+    }
+
+    /// The tree, to be saved with a document with `curves`
+    pub fn data(&self, curves: &[CurveData]) -> HistoryData {
+        let mut table = CurveTable::new(curves);
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|node| NodeData {
+                parent: node.parent,
+                splices: node.step.iter().map(|s| table.splice(s)).collect(),
+                removal: node.removal.clone(),
+            })
+            .collect();
+        HistoryData {
+            current: self.current,
+            nodes,
+            curves: table.into_curves(),
+        }
+    }
+
+    /// The saved tree of a document with `curves`, `None` if its steps can't be applied
+    /// to that many curves
+    pub fn from_data(data: &HistoryData, curves: &[Curve]) -> Option<Self> {
+        // what the splices refer to, the document's curves first
+        let table: Vec<Curve> = curves
+            .iter()
+            .cloned()
+            .chain(data.curves.iter().map(Curve::from))
+            .collect();
+        let nodes: Vec<Node> = data
+            .nodes
+            .iter()
+            .map(|node| {
+                Some(Node {
+                    parent: node.parent,
+                    step: node
+                        .splices
+                        .iter()
+                        .map(|s| s.splice(&table))
+                        .collect::<Option<_>>()?,
+                    removal: node.removal.clone(),
+                    next: None,
+                })
+            })
+            .collect::<Option<_>>()?;
+        let valid = !nodes.is_empty()
+            && data.current < nodes.len()
+            && nodes.iter().enumerate().all(|(i, node)| match node.parent {
+                None => i == 0,
+                Some(parent) => parent < i,
+            });
+        if !valid {
+            return None;
+        }
+
+        let mut history = History {
+            samples: nodes.iter().map(|node| samples(&node.step)).sum(),
+            nodes,
+            current: data.current,
+            open: None,
+        };
+        // every step has to fit the number of curves it's applied to
+        let mut lengths = vec![0; history.nodes.len()];
+        lengths[0] =
+            history
+                .ancestors(history.current)
+                .iter()
+                .try_fold(curves.len(), |length, &node| {
+                    history.nodes[node]
+                        .step
+                        .iter()
+                        .rev()
+                        .try_fold(length, |length, splice| {
+                            length
+                                .checked_sub(splice.inserted.len())
+                                .filter(|&length| splice.index <= length)
+                                .map(|length| length + splice.removed.len())
+                        })
+                })?;
+        for i in 1..history.nodes.len() {
+            let parent = history.nodes[i].parent?;
+            lengths[i] =
+                history.nodes[i]
+                    .step
+                    .iter()
+                    .try_fold(lengths[parent], |length, splice| {
+                        (splice.index + splice.removed.len() <= length)
+                            .then(|| length - splice.removed.len() + splice.inserted.len())
+                    })?;
+        }
+        history.trim();
+        Some(history)
+    }
+}
+
+/// Splices that undo `step`, in the order to apply them
+fn inverse(step: &Step) -> Step {
+    step.iter().rev().map(Splice::inverse).collect()
+}
+
+fn samples(step: &Step) -> usize {
+    step.iter().map(Splice::samples).sum()
+}
+
+/// What a step did, for the history panel
+fn label(node: &Node) -> String {
+    let removed: usize = node.step.iter().map(|s| s.removed.len()).sum();
+    let inserted: usize = node.step.iter().map(|s| s.inserted.len()).sum();
+    let curves = |count: usize| match count {
+        1 => "a curve".to_string(),
+        count => format!("{count} curves"),
+    };
+    match (node.parent, &node.removal, removed, inserted) {
+        (None, ..) => "Start".into(),
+        (_, Some(removal), ..) => format!("Delete {}", removal.layer.name),
+        (_, _, 0, inserted) => format!("Add {}", curves(inserted)),
+        (_, _, removed, 0) => format!("Remove {}", curves(removed)),
+        (_, _, removed, inserted) => format!("Replace {} with {inserted}", curves(removed)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::Sample;
+    use crate::layer::Layer;
+    use iced::{Color, Point};
+
+    /// A curve told apart from others by `x`
+    fn curve(x: f32) -> Curve {
+        let samples = vec![
+            Sample::new(Point::new(x, 0.), 0.5),
+            Sample::new(Point::new(x, 10.), 0.5),
+        ];
+        Curve::with_width(samples, Color::WHITE, 2.)
+    }
+
+    fn xs(curves: &[Curve]) -> Vec<f32> {
+        curves.iter().map(|c| c.samples()[0].point.x).collect()
+    }
+
+    /// Add a curve on top, as drawing does
+    fn draw(history: &mut History, curves: &mut Vec<Curve>, x: f32) {
+        let splice = Splice::insert(curves.len(), curve(x));
+        splice.apply(curves);
+        history.record(vec![splice]);
+    }
+
+    fn apply(change: Option<Change>, curves: &mut Vec<Curve>) {
+        for splice in change.expect("a change").splices {
+            splice.apply(curves);
+        }
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let (mut history, mut curves) = (History::default(), vec![]);
+        draw(&mut history, &mut curves, 1.);
+        draw(&mut history, &mut curves, 2.);
+        apply(history.undo(), &mut curves);
+        assert_eq!(xs(&curves), [1.]);
+        apply(history.redo(), &mut curves);
+        assert_eq!(xs(&curves), [1., 2.]);
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn steps_gather_the_edits_in_between() {
+        let (mut history, mut curves) = (History::default(), vec![]);
+        history.begin();
+        draw(&mut history, &mut curves, 1.);
+        draw(&mut history, &mut curves, 2.);
+        history.end();
+        apply(history.undo(), &mut curves);
+        assert!(curves.is_empty());
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn trim_forgets_the_oldest_steps() {
+        let (mut history, mut curves) = (History::default(), vec![]);
+        let extra = 10;
+        for x in 0..MAX_STEPS + extra {
+            draw(&mut history, &mut curves, x as f32);
+        }
+        assert_eq!(history.entries().len(), MAX_STEPS + 1);
+        while history.can_undo() {
+            apply(history.undo(), &mut curves);
+        }
+        assert_eq!(curves.len(), extra);
+    }
+
+    #[test]
+    fn trim_cuts_branches_left_behind() {
+        let (mut history, mut curves) = (History::default(), vec![]);
+        draw(&mut history, &mut curves, 1.);
+        draw(&mut history, &mut curves, 2.);
+        apply(history.undo(), &mut curves);
+        for x in 0..MAX_STEPS {
+            draw(&mut history, &mut curves, x as f32);
+        }
+        let entries = history.entries();
+        assert_eq!(entries.len(), MAX_STEPS + 1);
+        assert!(entries.iter().all(|e| e.depth == 0));
+        assert!(history.can_undo());
+    }
+
+    #[test]
+    fn drawing_after_undo_branches() {
+        let (mut history, mut curves) = (History::default(), vec![]);
+        draw(&mut history, &mut curves, 1.);
+        draw(&mut history, &mut curves, 2.);
+        apply(history.undo(), &mut curves);
+        draw(&mut history, &mut curves, 3.);
+        assert_eq!(xs(&curves), [1., 3.]);
+
+        let depths: Vec<usize> = history.entries().iter().map(|e| e.depth).collect();
+        assert_eq!(depths, [0, 0, 0, 1]);
+    }
+
+    #[test]
+    fn jump_goes_through_where_branches_meet() {
+        let (mut history, mut curves) = (History::default(), vec![]);
+        draw(&mut history, &mut curves, 1.);
+        draw(&mut history, &mut curves, 2.);
+        apply(history.undo(), &mut curves);
+        draw(&mut history, &mut curves, 3.);
+
+        apply(history.jump(2), &mut curves);
+        assert_eq!(xs(&curves), [1., 2.]);
+        apply(history.jump(0), &mut curves);
+        assert!(curves.is_empty());
+        assert!(!history.can_undo());
+        apply(history.jump(3), &mut curves);
+        assert_eq!(xs(&curves), [1., 3.]);
+        assert!(history.jump(3).is_none());
+        assert!(history.jump(4).is_none());
+
+        // redo follows the way last taken
+        apply(history.jump(2), &mut curves);
+        apply(history.undo(), &mut curves);
+        apply(history.redo(), &mut curves);
+        assert_eq!(xs(&curves), [1., 2.]);
+    }
+
+    #[test]
+    fn from_data_round_trips() {
+        let (mut history, mut curves) = (History::default(), vec![]);
+        draw(&mut history, &mut curves, 1.);
+        draw(&mut history, &mut curves, 2.);
+        apply(history.undo(), &mut curves);
+        draw(&mut history, &mut curves, 3.);
+        apply(history.jump(2), &mut curves);
+
+        let saved: Vec<CurveData> = curves.iter().map(CurveData::from).collect();
+        let data = history.data(&saved);
+        // the curves on the canvas are referred to, not stored again
+        assert_eq!(data.curves.len(), 1);
+
+        let mut loaded = History::from_data(&data, &curves).expect("a history");
+        assert_eq!(loaded.entries().len(), 4);
+        apply(loaded.jump(3), &mut curves);
+        assert_eq!(xs(&curves), [1., 3.]);
+        apply(loaded.undo(), &mut curves);
+        apply(loaded.undo(), &mut curves);
+        assert!(curves.is_empty());
+        assert!(!loaded.can_undo());
+    }
+
+    #[test]
+    fn from_data_refuses_histories_that_dont_fit() {
+        let (mut history, mut curves) = (History::default(), vec![]);
+        draw(&mut history, &mut curves, 1.);
+        draw(&mut history, &mut curves, 2.);
+        let saved: Vec<CurveData> = curves.iter().map(CurveData::from).collect();
+        let data = history.data(&saved);
+
+        assert!(History::from_data(&data, &curves).is_some());
+        // fewer curves than the steps inserted
+        assert!(History::from_data(&data, &curves[..1]).is_none());
+
+        let mut unknown = data.clone();
+        unknown.nodes[1].splices[0].inserted[0] = 7;
+        assert!(History::from_data(&unknown, &curves).is_none());
+
+        let mut current = data.clone();
+        current.current = 3;
+        assert!(History::from_data(&current, &curves).is_none());
+
+        let mut cycle = data.clone();
+        cycle.nodes[1].parent = Some(2);
+        assert!(History::from_data(&cycle, &curves).is_none());
+
+        let mut roots = data;
+        roots.nodes[1].parent = None;
+        assert!(History::from_data(&roots, &curves).is_none());
+    }
+
+    #[test]
+    fn undo_restores_a_deleted_layer() {
+        let (mut history, mut curves) = (History::default(), vec![curve(1.).on_layer(1)]);
+        let removal = Removal {
+            position: 1,
+            layer: Layer::new(1, "Layer 2".into()),
+        };
+        let splice = Splice::remove(0, curves[0].clone());
+        splice.apply(&mut curves);
+        history.record_removal(vec![splice], removal);
+
+        let change = history.undo().expect("a change");
+        assert!(matches!(&change.layers[..], [LayerChange::Restore(r)] if r.layer.id == 1));
+        apply(Some(change), &mut curves);
+        assert_eq!(xs(&curves), [1.]);
+        let change = history.redo().expect("a change");
+        assert!(matches!(change.layers[..], [LayerChange::Remove(1)]));
+    }
+}
@@ -0,0 +1,723 @@
+//! SVG import
+//!
+//! `<path>`, `<polyline>`, `<polygon>`, `<line>`, `<rect>`, `<circle>` and `<ellipse>`
+//! elements are flattened into curves, with the transforms of the element and its
+//! ancestors applied and the root `viewBox` scaled to the root's size. Each subpath
+//! becomes its own curve, keeping the stroke color and width. Elements without a stroke
+//! follow their outline in their fill color instead, except the outlines monstera
+//! exports strokes as, which become the stroke they outline again. Elements with
+//! neither, other elements, text and gradients are skipped.
+use std::f32::consts::{FRAC_PI_4, PI, TAU};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use glam::{Affine2, Vec2};
+use iced::{Color, Point, Vector};
+use svgtypes::{SimplePathSegment, SimplifyingPathParser, TransformListToken};
+
+use crate::bezier::CubicBezier;
+use crate::curve::{Curve, Sample};
+use crate::export;
+
+pub const EXTENSION: &str = "svg";
+
+/// Spacing of the samples curves and circles are flattened to, in world units
+const FLATTEN_STEP: f32 = 1.0;
+/// Width of a stroke without a `stroke-width`, as in SVG
+const DEFAULT_STROKE_WIDTH: f32 = 1.0;
+/// Pairs of points compared to find where an outline is symmetric
+const SYMMETRY_PAIRS: usize = 128;
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(io::Error),
+    Xml(roxmltree::Error),
+    /// The file has none of the elements that can be imported
+    Empty,
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(error) => write!(f, "could not access the file: {error}"),
+            ImportError::Xml(error) => write!(f, "not a valid SVG: {error}"),
+            ImportError::Empty => write!(f, "there are no paths or shapes to import"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<io::Error> for ImportError {
+    fn from(error: io::Error) -> Self {
+        ImportError::Io(error)
+    }
+}
+
+impl From<roxmltree::Error> for ImportError {
+    fn from(error: roxmltree::Error) -> Self {
+        ImportError::Xml(error)
+    }
+}
+
+/// A stroke or fill
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Paint {
+    /// Neither the element nor its ancestors say
+    Unset,
+    None,
+    Color(Color),
+}
+
+/// Style inherited from ancestors
+#[derive(Debug, Clone, Copy)]
+struct Style {
+    transform: Affine2,
+    stroke: Paint,
+    fill: Paint,
+    stroke_width: f32,
+    stroke_opacity: f32,
+    fill_opacity: f32,
+    /// Within a group of stroke outlines monstera exported
+    exported: bool,
+}
+
+/// Import the SVG at `path`, see [`import`]
+pub fn load(path: &Path, at: Point, fallback: Color) -> Result<Vec<Curve>, ImportError> {
+    import(&fs::read_to_string(path)?, at, fallback)
+}
+
+/// Curves of an SVG document, moved so the top left of the drawing is at `at`.
+/// Elements that set neither a stroke nor a fill are drawn in `fallback`.
+pub fn import(svg: &str, at: Point, fallback: Color) -> Result<Vec<Curve>, ImportError> {
+    let document = roxmltree::Document::parse(svg)?;
+    let style = Style {
+        transform: view_box(document.root_element()),
+        stroke: Paint::Unset,
+        fill: Paint::Unset,
+        stroke_width: DEFAULT_STROKE_WIDTH,
+        stroke_opacity: 1.,
+        fill_opacity: 1.,
+        exported: false,
+    };
+    let mut curves = vec![];
+    collect(document.root_element(), style, fallback, &mut curves);
+
+    let bounds = export::bounds(&curves).ok_or(ImportError::Empty)?;
+    let offset = at - Point::new(bounds.x, bounds.y);
+    Ok(curves
+        .into_iter()
+        .map(|curve| {
+            let samples = curve
+                .samples()
+                .iter()
+                .map(|s| Sample::new(s.point + offset, s.pressure))
+                .collect();
+            Curve::with_width(samples, curve.color(), curve.width())
+        })
+        .collect())
+}
+
+/// Collect the curves of `node` and its descendants, in document order
+fn collect(node: roxmltree::Node, parent: Style, fallback: Color, curves: &mut Vec<Curve>) {
+    if matches!(
+        node.tag_name().name(),
+        "defs" | "clipPath" | "mask" | "marker" | "pattern" | "symbol"
+    ) || attribute(node, "display") == Some("none")
+    {
+        return;
+    }
+    let style = style(node, parent);
+
+    let polylines = polylines(node);
+    let color = match (style.stroke, style.fill) {
+        (Paint::Color(stroke), _) => Some(stroke.scale_alpha(style.stroke_opacity)),
+        (_, Paint::Color(fill)) => Some(fill.scale_alpha(style.fill_opacity)),
+        (Paint::None, Paint::None) => None,
+        _ => Some(fallback),
+    };
+    if let Some(color) = color.filter(|_| !polylines.is_empty()) {
+        let stroke_outline = style.exported
+            && !matches!(style.stroke, Paint::Color(_))
+            && matches!(style.fill, Paint::Color(_));
+        let scale = style.transform.matrix2.determinant().abs().sqrt();
+        let width = style.stroke_width * scale;
+        curves.extend(
+            polylines
+                .into_iter()
+                .map(|polyline| flatten(&polyline, style.transform))
+                .filter_map(|samples| match samples[..] {
+                    [] => None,
+                    [first, .., last] if stroke_outline && first.point == last.point => {
+                        outlined(&samples[..samples.len() - 1], color)
+                    }
+                    _ => Some(Curve::with_width(samples, color, width)),
+                }),
+        );
+    }
+    node.children()
+        .filter(roxmltree::Node::is_element)
+        .for_each(|child| collect(child, style, fallback, curves));
+}
+
+/// Style of `node`, from its attributes, its `style` attribute and its parent
+fn style(node: roxmltree::Node, parent: Style) -> Style {
+    let mut style = parent;
+    if let Some(transform) = node.attribute("transform") {
+        style.transform = parent.transform * transform_list(transform);
+    }
+    let paint = |value: &str, inherited: Paint| match svgtypes::Paint::from_str(value) {
+        Ok(svgtypes::Paint::None) => Paint::None,
+        Ok(svgtypes::Paint::Color(c)) => Paint::Color(Color::from_rgba8(
+            c.red,
+            c.green,
+            c.blue,
+            c.alpha as f32 / 255.,
+        )),
+        _ => inherited,
+    };
+    if let Some(stroke) = attribute(node, "stroke") {
+        style.stroke = paint(stroke, parent.stroke);
+    }
+    if let Some(fill) = attribute(node, "fill") {
+        style.fill = paint(fill, parent.fill);
+    }
+    if let Some(width) = number(attribute(node, "stroke-width")) {
+        style.stroke_width = width;
+    }
+    if let Some(opacity) = number(attribute(node, "stroke-opacity")) {
+        style.stroke_opacity = parent.stroke_opacity * opacity;
+    }
+    if let Some(opacity) = number(attribute(node, "fill-opacity")) {
+        style.fill_opacity = parent.fill_opacity * opacity;
+    }
+    if let Some(opacity) = number(attribute(node, "opacity")) {
+        style.stroke_opacity *= opacity;
+        style.fill_opacity *= opacity;
+    }
+    if let Some(class) = node.attribute("class") {
+        style.exported |= class
+            .split_whitespace()
+            .any(|class| class == export::svg::STROKE_CLASS);
+    }
+    style
+}
+
+/// Scaling from the `viewBox` of the root element to its `width` and `height`, keeping
+/// the aspect ratio unless `preserveAspectRatio` is `none`
+fn view_box(root: roxmltree::Node) -> Affine2 {
+    let Some(view_box) = root
+        .attribute("viewBox")
+        .and_then(|view_box| view_box.parse::<svgtypes::ViewBox>().ok())
+    else {
+        return Affine2::IDENTITY;
+    };
+    let (x, y) = (view_box.x as f32, view_box.y as f32);
+    let (w, h) = (view_box.w as f32, view_box.h as f32);
+    // a size in percent is relative to where the SVG is placed, there's nothing here
+    let size = |name| {
+        root.attribute(name)
+            .and_then(|value| value.parse::<svgtypes::Length>().ok())
+            .filter(|length| length.unit != svgtypes::LengthUnit::Percent)
+            .map(|length| length.number as f32)
+    };
+    // a missing side follows the aspect ratio of the view box
+    let (width, height) = match (size("width"), size("height")) {
+        (Some(width), Some(height)) => (width, height),
+        (Some(width), None) => (width, width * h / w),
+        (None, Some(height)) => (height * w / h, height),
+        (None, None) => (w, h),
+    };
+    let scale = match root.attribute("preserveAspectRatio") {
+        Some(aspect) if aspect.trim() == "none" => Vec2::new(width / w, height / h),
+        _ => Vec2::splat((width / w).min(height / h)),
+    };
+    if !scale.is_finite() || scale.x <= 0. || scale.y <= 0. {
+        return Affine2::IDENTITY;
+    }
+    Affine2::from_scale(scale) * Affine2::from_translation(Vec2::new(-x, -y))
+}
+
+/// A presentation attribute, from the `style` attribute if it's set there
+fn attribute<'a>(node: roxmltree::Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attribute("style")
+        .and_then(|style| {
+            style.split(';').find_map(|declaration| {
+                let (property, value) = declaration.split_once(':')?;
+                (property.trim() == name).then(|| value.trim())
+            })
+        })
+        .or_else(|| node.attribute(name))
+}
+
+fn number(value: Option<&str>) -> Option<f32> {
+    value
+        .and_then(|value| value.parse::<svgtypes::Length>().ok())
+        .map(|length| length.number as f32)
+}
+
+fn transform_list(text: &str) -> Affine2 {
+    svgtypes::TransformListParser::from(text)
+        .map_while(Result::ok)
+        .fold(Affine2::IDENTITY, |transform, token| {
+            transform
+                * match token {
+                    TransformListToken::Matrix { a, b, c, d, e, f } => {
+                        Affine2::from_cols_array(&[a, b, c, d, e, f].map(|v| v as f32))
+                    }
+                    TransformListToken::Translate { tx, ty } => {
+                        Affine2::from_translation(Vec2::new(tx as f32, ty as f32))
+                    }
+                    TransformListToken::Scale { sx, sy } => {
+                        Affine2::from_scale(Vec2::new(sx as f32, sy as f32))
+                    }
+                    TransformListToken::Rotate { angle } => {
+                        Affine2::from_angle((angle as f32).to_radians())
+                    }
+                    TransformListToken::SkewX { angle } => Affine2::from_cols_array(&[
+                        1.,
+                        0.,
+                        (angle as f32).to_radians().tan(),
+                        1.,
+                        0.,
+                        0.,
+                    ]),
+                    TransformListToken::SkewY { angle } => Affine2::from_cols_array(&[
+                        1.,
+                        (angle as f32).to_radians().tan(),
+                        0.,
+                        1.,
+                        0.,
+                        0.,
+                    ]),
+                }
+        })
+}
+
+/// A run of connected segments in the element's own coordinates
+enum Segment {
+    Line(Point),
+    Cubic(Point, Point, Point),
+}
+
+struct Polyline {
+    start: Point,
+    segments: Vec<Segment>,
+}
+
+impl Polyline {
+    fn new(start: Point) -> Self {
+        Polyline {
+            start,
+            segments: vec![],
+        }
+    }
+
+    fn through(points: impl IntoIterator<Item = Point>, close: bool) -> Option<Self> {
+        let mut points = points.into_iter();
+        let start = points.next()?;
+        let mut polyline = Polyline::new(start);
+        polyline.segments.extend(points.map(Segment::Line));
+        if close {
+            polyline.segments.push(Segment::Line(start));
+        }
+        Some(polyline)
+    }
+}
+
+/// The geometry of an element that can be imported, one polyline per subpath
+fn polylines(node: roxmltree::Node) -> Vec<Polyline> {
+    let length = |name| number(node.attribute(name)).unwrap_or(0.);
+    let points = |close| {
+        let points = node.attribute("points").unwrap_or_default();
+        Polyline::through(
+            svgtypes::PointsParser::from(points).map(|(x, y)| Point::new(x as f32, y as f32)),
+            close,
+        )
+    };
+    match node.tag_name().name() {
+        "path" => path(node.attribute("d").unwrap_or_default()),
+        "polyline" => points(false).into_iter().collect(),
+        "polygon" => points(true).into_iter().collect(),
+        "line" => Polyline::through(
+            [
+                Point::new(length("x1"), length("y1")),
+                Point::new(length("x2"), length("y2")),
+            ],
+            false,
+        )
+        .into_iter()
+        .collect(),
+        "rect" => {
+            let (x, y) = (length("x"), length("y"));
+            let (width, height) = (length("width"), length("height"));
+            if width <= 0. || height <= 0. {
+                return vec![];
+            }
+            Polyline::through(
+                [
+                    Point::new(x, y),
+                    Point::new(x + width, y),
+                    Point::new(x + width, y + height),
+                    Point::new(x, y + height),
+                ],
+                true,
+            )
+            .into_iter()
+            .collect()
+        }
+        "circle" => ellipse(
+            Point::new(length("cx"), length("cy")),
+            Vector::new(length("r"), length("r")),
+        ),
+        "ellipse" => ellipse(
+            Point::new(length("cx"), length("cy")),
+            Vector::new(length("rx"), length("ry")),
+        ),
+        _ => vec![],
+    }
+}
+
+fn path(data: &str) -> Vec<Polyline> {
+    let mut polylines: Vec<Polyline> = vec![];
+    let point = |x: f64, y: f64| Point::new(x as f32, y as f32);
+    // the simplifying parser makes everything absolute and turns arcs into cubics
+    // stop at the first error, like browsers do
+    for segment in SimplifyingPathParser::from(data).map_while(Result::ok) {
+        match segment {
+            SimplePathSegment::MoveTo { x, y } => polylines.push(Polyline::new(point(x, y))),
+            SimplePathSegment::LineTo { x, y } => {
+                if let Some(polyline) = polylines.last_mut() {
+                    polyline.segments.push(Segment::Line(point(x, y)));
+                }
+            }
+            SimplePathSegment::CurveTo {
+                x1,
+                y1,
+                x2,
+                y2,
+                x,
+                y,
+            } => {
+                if let Some(polyline) = polylines.last_mut() {
+                    polyline.segments.push(Segment::Cubic(
+                        point(x1, y1),
+                        point(x2, y2),
+                        point(x, y),
+                    ));
+                }
+            }
+            SimplePathSegment::Quadratic { x1, y1, x, y } => {
+                if let Some(polyline) = polylines.last_mut() {
+                    let from = match polyline.segments.last() {
+                        Some(Segment::Line(end)) | Some(Segment::Cubic(_, _, end)) => *end,
+                        None => polyline.start,
+                    };
+                    let (control, to) = (point(x1, y1), point(x, y));
+                    polyline.segments.push(Segment::Cubic(
+                        from + (control - from) * (2. / 3.),
+                        to + (control - to) * (2. / 3.),
+                        to,
+                    ));
+                }
+            }
+            SimplePathSegment::ClosePath => {
+                if let Some(polyline) = polylines.last_mut() {
+                    polyline.segments.push(Segment::Line(polyline.start));
+                    // drawing continues from the start of the closed subpath
+                    let start = polyline.start;
+                    polylines.push(Polyline::new(start));
+                }
+            }
+        }
+    }
+    polylines.retain(|polyline| !polyline.segments.is_empty());
+    polylines
+}
+
+/// An ellipse as four quarter arcs
+fn ellipse(center: Point, radii: Vector) -> Vec<Polyline> {
+    if radii.x <= 0. || radii.y <= 0. {
+        return vec![];
+    }
+    // cubic handle length for a quarter circle
+    const KAPPA: f32 = 0.552_284_8;
+    let at = |angle: f32| Vector::new(angle.cos() * radii.x, angle.sin() * radii.y);
+    let mut polyline = Polyline::new(center + at(0.));
+    for quarter in 0..4 {
+        let (from, to) = (quarter as f32 * TAU / 4., (quarter + 1) as f32 * TAU / 4.);
+        polyline.segments.push(Segment::Cubic(
+            center + at(from) + at(to) * KAPPA,
+            center + at(to) + at(from) * KAPPA,
+            center + at(to),
+        ));
+    }
+    vec![polyline]
+}
+
+/// The stroke a closed `outline` monstera exported was filled as. Its ends
+/// are where the outline mirrors itself, and the center line runs between the two
+/// sides, as wide as they are apart.
+fn outlined(outline: &[Sample], color: Color) -> Option<Curve> {
+    let points: Vec<Point> = outline.iter().map(|sample| sample.point).collect();
+    let count = points.len();
+    if count < 3 {
+        return None;
+    }
+    // arc length at each point, and back around to the first one
+    let mut arcs = vec![0.];
+    for i in 0..count {
+        arcs.push(arcs[i] + points[i].distance(points[(i + 1) % count]));
+    }
+    let perimeter = arcs[count];
+    if perimeter <= 0. {
+        return None;
+    }
+    let at = |arc: f32| {
+        let arc = arc.rem_euclid(perimeter);
+        let i = (arcs.partition_point(|&a| a <= arc) - 1).min(count - 1);
+        let t = (arc - arcs[i]) / (arcs[i + 1] - arcs[i]).max(f32::EPSILON);
+        points[i] + (points[(i + 1) % count] - points[i]) * t
+    };
+
+    // how far the outline within `reach` of `end` is from itself mirrored there, the
+    // sides of a stroke mirror each other around its ends
+    let asymmetry = |end: f32, reach: f32| -> f32 {
+        (1..SYMMETRY_PAIRS)
+            .map(|j| {
+                let along = reach * j as f32 / SYMMETRY_PAIRS as f32;
+                at(end + along).distance(at(end - along))
+            })
+            .sum()
+    };
+    let half = perimeter / 2.;
+    // about how wide the stroke is, its area is its length times its width
+    let area = (0..count)
+        .map(|i| {
+            let (a, b) = (points[i], points[(i + 1) % count]);
+            a.x * b.y - b.x * a.y
+        })
+        .sum::<f32>()
+        .abs()
+        / 2.;
+    let thickness = area / half;
+    // roughly comparing whole sides, then closely just around the cap, as the sides
+    // stop mirroring each other where the stroke bends
+    let most_symmetric = |from: f32, to: f32, reach: f32| {
+        let step = (to - from) / SYMMETRY_PAIRS as f32;
+        let best = |ends: &mut dyn Iterator<Item = f32>, reach: f32| {
+            ends.map(|end| (end, asymmetry(end, reach)))
+                .min_by(|a, b| a.1.total_cmp(&b.1))
+                .map_or(from, |(end, _)| end)
+        };
+        let coarse = best(
+            &mut (0..SYMMETRY_PAIRS).map(|i| from + i as f32 * step),
+            reach,
+        );
+        let (close, fine) = (thickness.clamp(FLATTEN_STEP, half), FLATTEN_STEP / 4.);
+        let steps = ((step + close * 2.) / fine).ceil() as i32;
+        best(
+            &mut (-steps..=steps).map(|i| coarse + i as f32 * fine),
+            close,
+        )
+    };
+    let start = most_symmetric(0., perimeter, half);
+    // the other end is about halfway around
+    let end = most_symmetric(start + half / 2., start + half * 1.5, half / 4.);
+    let end = start + (end - start).rem_euclid(perimeter);
+
+    // one side from the start to the end, the other the rest of the way around, with
+    // whether each point is on a round cap. A cap ends where it's a quarter turn from
+    // the end it mirrors itself at.
+    let side = |length: f32, sign: f32| -> Vec<(Point, bool)> {
+        let steps = (length / FLATTEN_STEP).ceil().max(1.) as usize;
+        (0..=steps)
+            .map(|j| {
+                let along = length * j as f32 / steps as f32;
+                let back = length - along;
+                let on_cap = match along <= back {
+                    true => along <= FRAC_PI_4 * at(start + along).distance(at(start - along)),
+                    false => back <= FRAC_PI_4 * at(end - back).distance(at(end + back)),
+                };
+                (at(start + along * sign), on_cap)
+            })
+            .collect()
+    };
+    let left = side(end - start, 1.);
+    let right = side(perimeter - (end - start), -1.);
+
+    // each point of one side across from the nearest point of the other around as far
+    // along it, leaving out the caps as a stroke draws its own
+    let window = (right.len() / 8).max(8);
+    let across = |i: usize, point: Point| {
+        let along = i * (right.len() - 1) / (left.len() - 1).max(1);
+        let other = right[along.saturating_sub(window)..(along + window + 1).min(right.len())]
+            .iter()
+            .filter(|(_, on_cap)| !on_cap)
+            .map(|&(other, _)| other)
+            .min_by(|a, b| point.distance(*a).total_cmp(&point.distance(*b)))?;
+        let middle = Point::new((point.x + other.x) / 2., (point.y + other.y) / 2.);
+        Some((middle, point.distance(other)))
+    };
+    let mut center: Vec<(Point, f32)> = left
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, on_cap))| !on_cap)
+        .filter_map(|(i, &(point, _))| across(i, point))
+        .collect();
+    if center.is_empty() {
+        // a dot, it's all cap
+        let middle = Point::new(
+            points.iter().map(|p| p.x).sum::<f32>() / count as f32,
+            points.iter().map(|p| p.y).sum::<f32>() / count as f32,
+        );
+        center.push((middle, perimeter / PI));
+    }
+
+    // the widest the stroke is, but for the odd point across from the wrong spot
+    let mut widths: Vec<f32> = center.iter().map(|(_, width)| *width).collect();
+    widths.sort_by(f32::total_cmp);
+    let width = widths[(widths.len() - 1) * 19 / 20];
+    if width <= 0. {
+        return None;
+    }
+    let mut samples: Vec<Sample> = center
+        .into_iter()
+        .map(|(point, sample_width)| Sample::new(point, Curve::pressure_for(width, sample_width)))
+        .collect();
+    samples.dedup_by(|a, b| a.point == b.point);
+    Some(Curve::with_width(samples, color, width))
+}
+
+/// Samples along a polyline in world coordinates, at full pressure so the stroke
+/// keeps its width
+fn flatten(polyline: &Polyline, transform: Affine2) -> Vec<Sample> {
+    let apply = |point: Point| {
+        let point = transform.transform_point2(Vec2::new(point.x, point.y));
+        Sample::new(Point::new(point.x, point.y), 1.)
+    };
+    let mut samples = vec![apply(polyline.start)];
+    for segment in &polyline.segments {
+        let from = samples[samples.len() - 1];
+        match *segment {
+            Segment::Line(to) => samples.push(apply(to)),
+            Segment::Cubic(control_a, control_b, to) => {
+                let bezier = CubicBezier {
+                    from,
+                    control_a: apply(control_a).point,
+                    control_b: apply(control_b).point,
+                    to: apply(to),
+                };
+                samples.extend(bezier.flatten(FLATTEN_STEP));
+            }
+        }
+    }
+    samples.dedup_by(|a, b| a.point == b.point);
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iced::Rectangle;
+
+    fn svg(body: &str) -> String {
+        format!(r#"<svg xmlns="http://www.w3.org/2000/svg">{body}</svg>"#)
+    }
+
+    fn curves(body: &str) -> Vec<Curve> {
+        import(&svg(body), Point::ORIGIN, Color::WHITE).expect("curves")
+    }
+
+    fn size(curve: &Curve) -> (f32, f32) {
+        let Rectangle { width, height, .. } = curve.bounds().expect("bounds");
+        (width, height)
+    }
+
+    fn assert_near(a: f32, b: f32, tolerance: f32) {
+        assert!(
+            (a - b).abs() <= tolerance,
+            "{a} is not within {tolerance} of {b}"
+        );
+    }
+
+    #[test]
+    fn filled_rect_keeps_its_outline() {
+        let curves = curves(r#"<rect width="100" height="50" fill="red"/>"#);
+        assert_eq!(curves.len(), 1);
+        assert_eq!(curves[0].color(), Color::from_rgb8(255, 0, 0));
+        assert_eq!(curves[0].width(), DEFAULT_STROKE_WIDTH);
+        // the outline, plus half the default width on each side
+        let (width, height) = size(&curves[0]);
+        assert_near(width, 101., 0.01);
+        assert_near(height, 51., 0.01);
+    }
+
+    #[test]
+    fn filled_triangle_keeps_its_outline() {
+        let curves = curves(r#"<polygon points="0,0 100,0 50,80" fill="blue"/>"#);
+        assert_eq!(curves.len(), 1);
+        let (width, height) = size(&curves[0]);
+        assert_near(width, 101., 0.01);
+        assert_near(height, 81., 0.01);
+    }
+
+    #[test]
+    fn filled_outline_with_hole_keeps_both_outlines() {
+        let curves = curves(
+            r#"<path d="M0 0 H100 V100 H0 Z M25 25 V75 H75 V25 Z" fill="green" fill-rule="evenodd"/>"#,
+        );
+        assert_eq!(curves.len(), 2);
+        let (outer, inner) = (size(&curves[0]), size(&curves[1]));
+        assert_near(outer.0, 101., 0.01);
+        assert_near(outer.1, 101., 0.01);
+        assert_near(inner.0, 51., 0.01);
+        assert_near(inner.1, 51., 0.01);
+    }
+
+    #[test]
+    fn stroked_path_keeps_stroke_style() {
+        let curves = curves(
+            r##"<path d="M0 0 L40 0" stroke="#00ff00" stroke-width="4" stroke-opacity="0.5" fill="red"/>"##,
+        );
+        assert_eq!(curves.len(), 1);
+        assert_eq!(curves[0].width(), 4.);
+        assert_eq!(curves[0].color(), Color::from_rgba8(0, 255, 0, 0.5));
+        assert_eq!(curves[0].samples().len(), 2);
+    }
+
+    #[test]
+    fn view_box_is_scaled_to_the_size() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10" width="100" height="100">
+            <line x1="0" y1="0" x2="10" y2="0" stroke="black"/></svg>"#;
+        let curves = import(svg, Point::ORIGIN, Color::WHITE).expect("curves");
+        assert_eq!(curves[0].width(), 10.);
+        assert_near(size(&curves[0]).0, 110., 0.01);
+    }
+
+    #[test]
+    fn exported_stroke_is_imported_as_the_stroke() {
+        let samples = (0..=100)
+            .map(|x| Sample::new(Point::new(x as f32, 0.), 1.))
+            .collect();
+        let stroke = Curve::with_width(samples, Color::from_rgb(1., 0., 0.), 10.);
+        let svg = export::svg::export(std::slice::from_ref(&stroke), None).expect("an svg");
+        assert!(svg.contains(export::svg::STROKE_CLASS));
+
+        let curves = import(&svg, Point::ORIGIN, Color::WHITE).expect("curves");
+        assert_eq!(curves.len(), 1);
+        assert_near(curves[0].width(), stroke.width(), 1.);
+        let (width, height) = size(&curves[0]);
+        let (expected_width, expected_height) = size(&stroke);
+        assert_near(width, expected_width, 2.);
+        assert_near(height, expected_height, 2.);
+    }
+
+    #[test]
+    fn nothing_to_import() {
+        let result = import(&svg("<text>hi</text>"), Point::ORIGIN, Color::WHITE);
+        assert!(matches!(result, Err(ImportError::Empty)));
+    }
+}
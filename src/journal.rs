@@ -0,0 +1,338 @@
+//! Crash recovery journal
+//!
+//! Every edit to the canvas is appended to a journal file as a line of JSON. Each
+//! running monstera keeps its own journal, next to a lock file it holds an OS lock on
+//! while it runs. A lock file nobody holds at startup means that session didn't shut
+//! down cleanly, and its journal can be replayed to restore it. The session that
+//! claims it keeps it as its recovered journal until the user restores or discards
+//! it; if that never happens, the next start finds it again.
+use std::cmp::Reverse;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::document::{CurveData, Document};
+use crate::edit::Splice;
+use crate::layer::{Layer, LayerId, Layers};
+
+// suffixes of the files of a session, after its name
+const JOURNAL: &str = ".jsonl";
+const COMPACTING: &str = ".compacting.jsonl";
+const RECOVERED: &str = ".recovered.jsonl";
+const LOCK: &str = ".lock";
+
+/// Entries after which the journal is rewritten from a snapshot, so it doesn't grow
+/// with every edit of a long session
+const COMPACT_AFTER: usize = 1000;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+enum Entry {
+    /// The whole canvas, e.g. after opening a document
+    Snapshot { document: Document },
+    /// Replace `remove` curves from `index` on with `insert`
+    Splice {
+        index: usize,
+        remove: usize,
+        insert: Vec<CurveData>,
+    },
+    /// The layers changed, their curves stay as they are
+    Layers {
+        layers: Vec<Layer>,
+        active: LayerId,
+        #[serde(default)]
+        next_id: LayerId,
+    },
+}
+
+#[derive(Debug)]
+pub struct Journal {
+    directory: PathBuf,
+    /// Name of this session's files, unique to the process
+    session: String,
+    file: File,
+    /// Held locked until the session ends
+    _lock: File,
+    /// Entries in the journal file
+    entries: usize,
+}
+
+impl Journal {
+    /// Start journaling a session that begins with `document`. If another session
+    /// didn't shut down cleanly, what could be recovered from it is returned as well.
+    pub fn start(document: &Document) -> io::Result<(Journal, Option<Document>)> {
+        Self::start_in(state_directory(), document)
+    }
+
+    fn start_in(
+        directory: PathBuf,
+        document: &Document,
+    ) -> io::Result<(Journal, Option<Document>)> {
+        fs::create_dir_all(&directory)?;
+
+        let started = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let session = format!("{}-{started}", std::process::id());
+        let lock = File::create(directory.join(format!("{session}{LOCK}")))?;
+        lock.try_lock()?;
+
+        let recovered = directory.join(format!("{session}{RECOVERED}"));
+        let recovery = match claim_crashed(&directory, &recovered) {
+            true => {
+                let recovery =
+                    replay(File::open(&recovered)?).filter(|document| !document.curves.is_empty());
+                // there's nothing to offer, so nothing to keep around until it's resolved
+                if recovery.is_none() {
+                    let _ = fs::remove_file(&recovered);
+                }
+                recovery
+            }
+            false => None,
+        };
+
+        let mut journal = Journal {
+            file: File::create(directory.join(format!("{session}{JOURNAL}")))?,
+            directory,
+            session,
+            _lock: lock,
+            entries: 0,
+        };
+        journal.snapshot(document)?;
+        Ok((journal, recovery))
+    }
+
+    /// Path of this session's file ending in `suffix`
+    fn path(&self, suffix: &str) -> PathBuf {
+        self.directory.join(format!("{}{suffix}", self.session))
+    }
+
+    pub fn snapshot(&mut self, document: &Document) -> io::Result<()> {
+        self.append(&Entry::Snapshot {
+            document: document.clone(),
+        })
+    }
+
+    pub fn splice(&mut self, splice: &Splice) -> io::Result<()> {
+        self.append(&Entry::Splice {
+            index: splice.index,
+            remove: splice.removed.len(),
+            insert: splice.inserted.iter().map(CurveData::from).collect(),
+        })
+    }
+
+    pub fn layers(&mut self, layers: &Layers) -> io::Result<()> {
+        self.append(&Entry::Layers {
+            layers: layers.iter().cloned().collect(),
+            active: layers.active().id,
+            next_id: layers.next_id(),
+        })
+    }
+
+    fn append(&mut self, entry: &Entry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        // one write per entry, so a crash can at worst cut off the last line
+        self.file.write_all(&line)?;
+        self.entries += 1;
+        Ok(())
+    }
+
+    /// Whether the journal should be compacted
+    pub fn is_long(&self) -> bool {
+        self.entries >= COMPACT_AFTER
+    }
+
+    /// Rewrite the journal as a single snapshot of `document`, what replaying it
+    /// would give. The old journal is kept until the new one is complete.
+    pub fn compact(&mut self, document: &Document) -> io::Result<()> {
+        let compacting = self.path(COMPACTING);
+        self.file = File::create(&compacting)?;
+        self.entries = 0;
+        self.snapshot(document)?;
+        fs::rename(compacting, self.path(JOURNAL))
+    }
+
+    /// Forget the journal of the previous session, once it's been restored or declined
+    pub fn discard_recovered(&self) -> io::Result<()> {
+        match fs::remove_file(self.path(RECOVERED)) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        }
+    }
+
+    /// Mark the session as cleanly shut down. A recovered journal that was neither
+    /// restored nor discarded is left for the next start to offer again.
+    pub fn finish(&self) -> io::Result<()> {
+        fs::remove_file(self.path(JOURNAL))?;
+        fs::remove_file(self.path(LOCK))
+    }
+}
+
+/// Move the latest journal of a session that didn't shut down cleanly, or that
+/// recovered one and wasn't told what to do with it, to `recovered`, whether there
+/// was one. Sessions still running hold their lock, older journals are left for the
+/// next start.
+fn claim_crashed(directory: &Path, recovered: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(directory) else {
+        return false;
+    };
+    let mut journals: Vec<_> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?;
+            let session = match name.strip_suffix(RECOVERED) {
+                Some(session) => session,
+                None if name.ends_with(COMPACTING) => return None,
+                None => name.strip_suffix(JOURNAL)?,
+            }
+            .to_owned();
+            let modified = fs::metadata(&path).and_then(|m| m.modified()).ok()?;
+            Some((modified, session, path))
+        })
+        .collect();
+    journals.sort_by_key(|(modified, ..)| Reverse(*modified));
+
+    journals.into_iter().any(|(_, session, journal)| {
+        let path = |suffix: &str| directory.join(format!("{session}{suffix}"));
+        // a running session holds its lock, one that shut down cleanly removed it
+        let _lock = match File::open(path(LOCK)) {
+            Ok(lock) => match lock.try_lock() {
+                Ok(()) => Some(lock),
+                Err(_) => return false,
+            },
+            Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+            Err(_) => return false,
+        };
+        // another start may have claimed the journal between listing and locking
+        if fs::rename(&journal, recovered).is_err() {
+            return false;
+        }
+        if !path(JOURNAL).exists() && !path(RECOVERED).exists() {
+            let _ = fs::remove_file(path(COMPACTING));
+            let _ = fs::remove_file(path(LOCK));
+        }
+        true
+    })
+}
+
+/// Rebuild the canvas from a journal, up to the first entry that can't be read
+fn replay(file: File) -> Option<Document> {
+    let mut document: Option<Document> = None;
+    for line in BufReader::new(file).lines() {
+        let Some(entry) = line.ok().and_then(|l| serde_json::from_str(&l).ok()) else {
+            break;
+        };
+        match entry {
+            Entry::Snapshot { document: snapshot } => document = Some(snapshot),
+            Entry::Splice {
+                index,
+                remove,
+                insert,
+            } => {
+                let curves = &mut document.as_mut()?.curves;
+                if index + remove > curves.len() {
+                    break;
+                }
+                curves.splice(index..index + remove, insert);
+            }
+            Entry::Layers {
+                layers,
+                active,
+                next_id,
+            } => {
+                let document = document.as_mut()?;
+                document.layers = layers;
+                document.active_layer = active;
+                document.next_layer_id = next_id;
+            }
+        }
+    }
+    document
+}
+
+/// `$XDG_STATE_HOME/monstera`, or `~/.local/state/monstera`
+fn state_directory() -> PathBuf {
+    let state = std::env::var_os("XDG_STATE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .unwrap_or_else(std::env::temp_dir);
+    state.join("monstera")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::{Curve, Sample};
+    use iced::{Color, Point};
+
+    fn document() -> Document {
+        Document::new([0., 0., 300.], &[Color::WHITE], Color::WHITE, &[])
+    }
+
+    fn curve() -> Curve {
+        let samples = vec![
+            Sample::new(Point::new(0., 0.), 0.5),
+            Sample::new(Point::new(10., 0.), 0.5),
+        ];
+        Curve::with_width(samples, Color::WHITE, 2.)
+    }
+
+    /// Names of the files left in `directory`
+    fn files(directory: &Path) -> Vec<String> {
+        let mut files: Vec<String> = fs::read_dir(directory)
+            .expect("a directory")
+            .map(|entry| {
+                entry
+                    .expect("an entry")
+                    .file_name()
+                    .into_string()
+                    .expect("a name")
+            })
+            .collect();
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn recovery_survives_until_resolved() {
+        let directory = tempfile::tempdir().expect("a directory");
+        let start = || Journal::start_in(directory.path().into(), &document()).expect("a journal");
+
+        let (mut crashed, recovery) = start();
+        assert!(recovery.is_none());
+        crashed
+            .splice(&Splice::insert(0, curve()))
+            .expect("a splice");
+        // crashing leaves the journal and an unheld lock behind
+        drop(crashed);
+
+        let (quit, recovery) = start();
+        assert_eq!(recovery.expect("a recovery").curves.len(), 1);
+        // quitting without restoring or discarding keeps it for the next start
+        quit.finish().expect("a clean shutdown");
+        assert_eq!(files(directory.path()).len(), 1);
+
+        let (resolved, recovery) = start();
+        assert_eq!(recovery.expect("a recovery").curves.len(), 1);
+        resolved.discard_recovered().expect("discarded");
+        resolved.finish().expect("a clean shutdown");
+        assert!(files(directory.path()).is_empty());
+    }
+
+    #[test]
+    fn empty_recovery_is_removed() {
+        let directory = tempfile::tempdir().expect("a directory");
+        let start = || Journal::start_in(directory.path().into(), &document()).expect("a journal");
+
+        drop(start());
+        let (journal, recovery) = start();
+        assert!(recovery.is_none());
+        journal.finish().expect("a clean shutdown");
+        assert!(files(directory.path()).is_empty());
+    }
+}
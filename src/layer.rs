@@ -0,0 +1,215 @@
+//! Layers of the canvas
+//!
+//! Curves stay in one list, each tagged with the id of its layer, so edits, history
+//! and the journal don't need to know about layers. Layers decide the order curves are
+//! drawn in and whether they're shown and can be changed.
+use serde::{Deserialize, Serialize};
+
+use crate::curve::Curve;
+
+pub type LayerId = u32;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Layer {
+    pub id: LayerId,
+    pub name: String,
+    pub visible: bool,
+    /// Curves of a locked layer are shown but can't be drawn on or erased
+    pub locked: bool,
+    /// In `0.0..=1.0`, applied to each curve on its own
+    pub opacity: f32,
+}
+
+impl Layer {
+    pub fn new(id: LayerId, name: String) -> Self {
+        Layer {
+            id,
+            name,
+            visible: true,
+            locked: false,
+            opacity: 1.,
+        }
+    }
+
+    /// Whether curves can be added to or removed from the layer
+    pub fn is_editable(&self) -> bool {
+        self.visible && !self.locked
+    }
+}
+
+/// A layer that was deleted, with where it was so deleting it can be undone
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Removal {
+    /// Index of the layer, bottom first
+    pub position: usize,
+    pub layer: Layer,
+}
+
+/// The layers of a canvas, at least one, with one of them active
+#[derive(Debug, Clone)]
+pub struct Layers {
+    /// Bottom first
+    layers: Vec<Layer>,
+    /// Layer new curves are drawn on
+    active: LayerId,
+    /// Id of the next layer added. Ids are never reused, so a deleted layer brought
+    /// back by undo gets its curves back and not those of a newer layer.
+    next_id: LayerId,
+}
+
+impl Default for Layers {
+    fn default() -> Self {
+        Layers {
+            layers: vec![Layer::new(0, "Layer 1".into())],
+            active: 0,
+            next_id: 1,
+        }
+    }
+}
+
+impl Layers {
+    /// Layers as saved, the default single layer if there are none. `next_id` is
+    /// raised past the ids of the layers if needed.
+    pub fn new(layers: Vec<Layer>, active: LayerId, next_id: LayerId) -> Self {
+        if layers.is_empty() {
+            return Layers {
+                next_id: next_id.max(1),
+                ..Layers::default()
+            };
+        }
+        let active = match layers.iter().any(|layer| layer.id == active) {
+            true => active,
+            false => layers[layers.len() - 1].id,
+        };
+        let next_id = layers
+            .iter()
+            .map(|layer| layer.id + 1)
+            .fold(next_id, LayerId::max);
+        Layers {
+            layers,
+            active,
+            next_id,
+        }
+    }
+
+    /// Bottom first
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Layer> {
+        self.layers.iter()
+    }
+
+    pub fn next_id(&self) -> LayerId {
+        self.next_id
+    }
+
+    pub fn active(&self) -> &Layer {
+        self.get(self.active)
+    }
+
+    pub fn set_active(&mut self, id: LayerId) {
+        if self.layers.iter().any(|layer| layer.id == id) {
+            self.active = id;
+        }
+    }
+
+    /// The layer with `id`. Curves on a layer that no longer exists, e.g. brought back
+    /// by undoing the layer's deletion, are on the bottom layer.
+    pub fn get(&self, id: LayerId) -> &Layer {
+        self.layers
+            .iter()
+            .find(|layer| layer.id == id)
+            .unwrap_or(&self.layers[0])
+    }
+
+    pub fn get_mut(&mut self, id: LayerId) -> Option<&mut Layer> {
+        self.layers.iter_mut().find(|layer| layer.id == id)
+    }
+
+    /// Add an empty layer above the active one and make it active
+    pub fn add(&mut self) -> LayerId {
+        let id = self.next_id;
+        self.next_id += 1;
+        let name = (1..)
+            .map(|n| format!("Layer {n}"))
+            .find(|name| self.layers.iter().all(|layer| &layer.name != name))
+            .unwrap_or_default();
+        let above = self
+            .position(self.active)
+            .map_or(self.layers.len(), |i| i + 1);
+        self.layers.insert(above, Layer::new(id, name));
+        self.active = id;
+        id
+    }
+
+    /// Remove a layer, unless it's the last one. The layer below becomes active.
+    pub fn remove(&mut self, id: LayerId) -> Option<Removal> {
+        match self.position(id) {
+            Some(position) if self.layers.len() > 1 => {
+                let layer = self.layers.remove(position);
+                if self.active == id {
+                    self.active = self.layers[position.saturating_sub(1)].id;
+                }
+                Some(Removal { position, layer })
+            }
+            _ => None,
+        }
+    }
+
+    /// Put a removed layer back where it was and make it active
+    pub fn restore(&mut self, removal: &Removal) {
+        if self.position(removal.layer.id).is_some() {
+            return;
+        }
+        let position = removal.position.min(self.layers.len());
+        self.layers.insert(position, removal.layer.clone());
+        self.active = removal.layer.id;
+        self.next_id = self.next_id.max(removal.layer.id + 1);
+    }
+
+    /// Move a layer one step up
+    pub fn raise(&mut self, id: LayerId) {
+        if let Some(position) = self.position(id) {
+            if position + 1 < self.layers.len() {
+                self.layers.swap(position, position + 1);
+            }
+        }
+    }
+
+    /// Move a layer one step down
+    pub fn lower(&mut self, id: LayerId) {
+        if let Some(position) = self.position(id) {
+            if position > 0 {
+                self.layers.swap(position, position - 1);
+            }
+        }
+    }
+
+    fn position(&self, id: LayerId) -> Option<usize> {
+        self.layers.iter().position(|layer| layer.id == id)
+    }
+
+    /// Whether the curve can be drawn on or erased
+    pub fn is_editable(&self, curve: &Curve) -> bool {
+        self.get(curve.layer()).is_editable()
+    }
+
+    /// Curves of visible layers in the order they're drawn, with their layer's opacity
+    pub fn compose<'a>(&'a self, curves: &'a [Curve]) -> impl Iterator<Item = (&'a Curve, f32)> {
+        self.layers
+            .iter()
+            .filter(|layer| layer.visible)
+            .flat_map(move |layer| {
+                curves
+                    .iter()
+                    .filter(move |curve| self.get(curve.layer()).id == layer.id)
+                    .map(move |curve| (curve, layer.opacity))
+            })
+    }
+
+    /// Copies of the curves of visible layers in the order they're drawn, faded by their
+    /// layer's opacity, e.g. to export what the canvas shows
+    pub fn composed(&self, curves: &[Curve]) -> Vec<Curve> {
+        self.compose(curves)
+            .map(|(curve, opacity)| curve.faded(opacity))
+            .collect()
+    }
+}
@@ -1,5 +1,19 @@
 pub mod bench;
+pub mod bezier;
+pub mod clip;
 pub mod curve;
+pub mod document;
+pub mod edit;
+pub mod export;
+pub mod geometry;
+pub mod history;
+pub mod import;
+pub mod journal;
+pub mod layer;
+pub mod playback;
+pub mod shape;
+pub mod stabilizer;
+pub mod transform;
 pub mod wayland;
 pub mod widgets;
 pub mod world;
@@ -1,8 +1,9 @@
 use iced::{theme::palette, Theme};
 use iced_wgpu::wgpu::util::DeviceExt;
 use monstera::bench::Bench;
-use monstera::world::World;
+use monstera::world::{self, World};
 
+mod cli;
 mod scene;
 use scene::Scene;
 
@@ -25,11 +26,22 @@ use winit::{
 };
 
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+/// Time between frames while the world animates
+const FRAME: Duration = Duration::from_millis(16);
 
 pub fn main() -> Result<(), winit::error::EventLoopError> {
     tracing_subscriber::fmt::init();
 
+    if let Some(result) = cli::run(std::env::args().skip(1)) {
+        if let Err(error) = result {
+            eprintln!("{error}");
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     // Initialize winit
     let event_loop = EventLoop::new()?;
     let mut runner = Runner::Loading;
@@ -63,6 +75,41 @@ enum Runner {
 }
 
 impl winit::application::ApplicationHandler for Runner {
+    /// Keep drawing frames while the world animates, e.g. during playback
+    fn about_to_wait(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
+        let Self::Ready {
+            window,
+            renderer,
+            state,
+            theme,
+            viewport,
+            cursor_position,
+            clipboard,
+            debug,
+            ..
+        } = self
+        else {
+            return;
+        };
+        if !state.program().is_animating() {
+            event_loop.set_control_flow(ControlFlow::Wait);
+            return;
+        }
+
+        state.queue_message(world::Message::Tick(Instant::now()));
+        update(
+            state,
+            viewport,
+            *cursor_position,
+            renderer,
+            theme,
+            clipboard,
+            debug,
+        );
+        window.request_redraw();
+        event_loop.set_control_flow(ControlFlow::WaitUntil(Instant::now() + FRAME));
+    }
+
     fn resumed(&mut self, event_loop: &winit::event_loop::ActiveEventLoop) {
         if let Self::Loading = self {
             let window = Arc::new(
@@ -176,7 +223,8 @@ impl winit::application::ApplicationHandler for Runner {
             let scene = Scene::new(&device, format, bind_group, &bind_group_layout);
 
             //let controls = Controls::new();
-            let world = World::default();
+            let mut world = World::default();
+            world.start_journal();
 
             // Initialize iced
             let mut debug = Debug::new();
@@ -206,7 +254,7 @@ impl winit::application::ApplicationHandler for Runner {
             };
             let theme = Theme::custom("my_theme".into(), palette);
 
-            // You should change this if you want to render continuously
+            // frames are only drawn on input, unless the world animates, see `about_to_wait`
             event_loop.set_control_flow(ControlFlow::Wait);
 
             *self = Self::Ready {
@@ -361,6 +409,23 @@ impl winit::application::ApplicationHandler for Runner {
             WindowEvent::CursorMoved { position, .. } => {
                 *cursor_position = Some(position);
             }
+            //// iced drops touch force, so forward it to the world ourselves
+            WindowEvent::Touch(winit::event::Touch {
+                force: Some(force), ..
+            }) => {
+                // queued messages are handled after the events of the same update, so the
+                // force is handled on its own, before the touch it belongs to
+                state.queue_message(world::Message::Pressure(force.normalized() as f32));
+                update(
+                    state,
+                    viewport,
+                    *cursor_position,
+                    renderer,
+                    theme,
+                    clipboard,
+                    debug,
+                );
+            }
             WindowEvent::ModifiersChanged(new_modifiers) => {
                 *modifiers = new_modifiers.state();
             }
@@ -368,6 +433,7 @@ impl winit::application::ApplicationHandler for Runner {
                 *resized = true;
             }
             WindowEvent::CloseRequested => {
+                state.program().finish_journal();
                 event_loop.exit();
             }
             _ => {}
@@ -384,17 +450,12 @@ impl winit::application::ApplicationHandler for Runner {
         // If there are events pending
         if !state.is_queue_empty() {
             // We update iced
-            let _task = state.update(
-                viewport.logical_size(),
-                cursor_position
-                    .map(|p| conversion::cursor_position(p, viewport.scale_factor()))
-                    .map(mouse::Cursor::Available)
-                    .unwrap_or(mouse::Cursor::Unavailable),
+            update(
+                state,
+                viewport,
+                *cursor_position,
                 renderer,
                 theme,
-                &renderer::Style {
-                    text_color: Color::WHITE,
-                },
                 clipboard,
                 debug,
             );
@@ -408,3 +469,29 @@ impl winit::application::ApplicationHandler for Runner {
         // println!("{}", bench.summary());
     }
 }
+
+/// Let iced handle the queued events and messages
+fn update(
+    state: &mut program::State<World>,
+    viewport: &Viewport,
+    cursor_position: Option<winit::dpi::PhysicalPosition<f64>>,
+    renderer: &mut Renderer,
+    theme: &iced::Theme,
+    clipboard: &mut Clipboard,
+    debug: &mut Debug,
+) {
+    let _task = state.update(
+        viewport.logical_size(),
+        cursor_position
+            .map(|p| conversion::cursor_position(p, viewport.scale_factor()))
+            .map(mouse::Cursor::Available)
+            .unwrap_or(mouse::Cursor::Unavailable),
+        renderer,
+        theme,
+        &renderer::Style {
+            text_color: Color::WHITE,
+        },
+        clipboard,
+        debug,
+    );
+}
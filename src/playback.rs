@@ -0,0 +1,126 @@
+//! Replaying how a drawing was made
+//!
+//! Curves are redrawn in the order they were drawn, each at the pace its samples were
+//! drawn at. Long pauses between strokes are shortened, and curves without timestamps
+//! are drawn one after another at a steady pace.
+use crate::curve::Curve;
+
+/// Longest pause between two strokes, in seconds of playback
+const MAX_PAUSE: f64 = 1.;
+/// Pause before curves whose time isn't known
+const UNKNOWN_PAUSE: f64 = 0.25;
+
+/// A curve on the playback timeline
+#[derive(Debug, Clone, Copy)]
+struct Stroke {
+    /// Seconds into playback it starts being drawn
+    start: f64,
+    /// Offset from playback time to the time of its samples
+    offset: f64,
+    duration: f64,
+}
+
+#[derive(Debug)]
+pub struct Playback {
+    /// In the order they were drawn
+    curves: Vec<Curve>,
+    strokes: Vec<Stroke>,
+    /// Seconds into playback
+    position: f64,
+    /// Playback seconds per second
+    pub speed: f32,
+    pub playing: bool,
+    /// Number of curves that are completely drawn at `position`
+    drawn: usize,
+    /// The curve being drawn at `position`
+    partial: Option<Curve>,
+}
+
+impl Playback {
+    pub fn new(curves: &[Curve], speed: f32) -> Self {
+        let mut curves = curves.to_vec();
+        // unknown times sort first, they were there before anything that was timed
+        curves.sort_by(|a, b| a.time().total_cmp(&b.time()));
+
+        let mut position = 0.;
+        let mut previous: Option<&Curve> = None;
+        let strokes = curves
+            .iter()
+            .map(|curve| {
+                let pause = match previous {
+                    None => 0.,
+                    Some(previous) if previous.time() == 0. || curve.time() == 0. => UNKNOWN_PAUSE,
+                    Some(previous) => {
+                        (curve.time() - previous.time() - previous.duration()).clamp(0., MAX_PAUSE)
+                    }
+                };
+                let stroke = Stroke {
+                    start: position + pause,
+                    offset: curve.time() - (position + pause),
+                    duration: curve.duration(),
+                };
+                position = stroke.start + stroke.duration;
+                previous = Some(curve);
+                stroke
+            })
+            .collect();
+
+        let mut playback = Playback {
+            curves,
+            strokes,
+            position: 0.,
+            speed,
+            playing: true,
+            drawn: 0,
+            partial: None,
+        };
+        playback.seek(0.);
+        playback
+    }
+
+    /// Seconds the whole playback takes at normal speed
+    pub fn duration(&self) -> f64 {
+        self.strokes
+            .last()
+            .map_or(0., |stroke| stroke.start + stroke.duration)
+    }
+
+    pub fn position(&self) -> f64 {
+        self.position
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.duration()
+    }
+
+    /// Move `elapsed` seconds of real time on, at the playback speed
+    pub fn advance(&mut self, elapsed: f64) {
+        if self.playing {
+            self.seek(self.position + elapsed * self.speed as f64);
+            self.playing = !self.is_finished();
+        }
+    }
+
+    /// Jump to `position` seconds into playback
+    pub fn seek(&mut self, position: f64) {
+        self.position = position.clamp(0., self.duration());
+        self.drawn = self
+            .strokes
+            .partition_point(|stroke| stroke.start + stroke.duration <= self.position);
+        self.partial = self
+            .strokes
+            .get(self.drawn)
+            .filter(|stroke| stroke.start <= self.position)
+            .map(|stroke| self.curves[self.drawn].drawn_by(self.position + stroke.offset));
+    }
+
+    /// Curves that are completely drawn
+    pub fn drawn(&self) -> &[Curve] {
+        &self.curves[..self.drawn]
+    }
+
+    /// The curve being drawn
+    pub fn partial(&self) -> Option<&Curve> {
+        self.partial.as_ref()
+    }
+}
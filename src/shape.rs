@@ -0,0 +1,313 @@
+use std::f32::consts::{FRAC_PI_4, TAU};
+use std::fmt;
+
+use iced::widget::canvas::{path, Path};
+use iced::{Point, Radians, Vector};
+
+use crate::transform::Transform;
+
+/// Segments used to flatten an ellipse for hit testing
+const ELLIPSE_SEGMENTS: usize = 64;
+/// Length of an arrow head relative to the shaft, and its cap in world units
+const HEAD_RATIO: f32 = 0.25;
+const MAX_HEAD: f32 = 20.;
+/// Angle steps lines and rotations snap to with shift held
+pub const SNAP_ANGLE: f32 = std::f32::consts::PI / 12.;
+/// How close to a quarter turn a shape must be for its axes to count as the canvas'
+const ALIGNED: f32 = 1e-4;
+
+/// Which shape a shape tool draws
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShapeKind {
+    Rectangle,
+    Ellipse,
+    Arrow,
+    Polygon,
+}
+
+impl ShapeKind {
+    pub const ALL: [ShapeKind; 4] = [
+        ShapeKind::Rectangle,
+        ShapeKind::Ellipse,
+        ShapeKind::Arrow,
+        ShapeKind::Polygon,
+    ];
+}
+
+impl fmt::Display for ShapeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ShapeKind::Rectangle => "Rectangle",
+            ShapeKind::Ellipse => "Ellipse",
+            ShapeKind::Arrow => "Arrow",
+            ShapeKind::Polygon => "Polygon",
+        })
+    }
+}
+
+/// A shape kept as its parameters, so it stays exact and editable
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shape {
+    Rectangle {
+        center: Point,
+        /// Half the width and height, before rotation
+        half: Vector,
+        rotation: f32,
+    },
+    Ellipse {
+        center: Point,
+        radii: Vector,
+        rotation: f32,
+    },
+    /// A line with a head at `to`
+    Arrow { from: Point, to: Point, head: f32 },
+    /// A polygon with its corners evenly spaced around an ellipse
+    Polygon {
+        center: Point,
+        radii: Vector,
+        rotation: f32,
+        sides: u32,
+    },
+}
+
+impl Shape {
+    /// The shape dragged out from `start` to `end`. When `constrain`ed, boxes are kept
+    /// square and arrows snap to 45°.
+    pub fn from_drag(
+        kind: ShapeKind,
+        start: Point,
+        end: Point,
+        constrain: bool,
+        sides: u32,
+    ) -> Self {
+        let delta = end - start;
+        let half = match constrain {
+            true => {
+                let side = delta.x.abs().max(delta.y.abs()) / 2.;
+                Vector::new(side.copysign(delta.x), side.copysign(delta.y))
+            }
+            false => delta * 0.5,
+        };
+        let center = start + half;
+        let half = Vector::new(half.x.abs(), half.y.abs());
+
+        match kind {
+            ShapeKind::Rectangle => Shape::Rectangle {
+                center,
+                half,
+                rotation: 0.,
+            },
+            ShapeKind::Ellipse => Shape::Ellipse {
+                center,
+                radii: half,
+                rotation: 0.,
+            },
+            ShapeKind::Polygon => Shape::Polygon {
+                center,
+                radii: half,
+                // point up
+                rotation: -std::f32::consts::FRAC_PI_2,
+                sides: sides.max(3),
+            },
+            ShapeKind::Arrow => {
+                let to = match constrain {
+                    true => snap_angle(start, end, FRAC_PI_4),
+                    false => end,
+                };
+                Shape::Arrow {
+                    from: start,
+                    to,
+                    head: (start.distance(to) * HEAD_RATIO).min(MAX_HEAD),
+                }
+            }
+        }
+    }
+
+    /// Whether the shape has no extent, e.g. from a click without a drag
+    pub fn is_degenerate(&self) -> bool {
+        match *self {
+            Shape::Rectangle { half, .. } => half.x == 0. && half.y == 0.,
+            Shape::Ellipse { radii, .. } | Shape::Polygon { radii, .. } => {
+                radii.x == 0. && radii.y == 0.
+            }
+            Shape::Arrow { from, to, .. } => from == to,
+        }
+    }
+
+    /// The shape moved, scaled and rotated by `transform`, `None` if it would no longer
+    /// be this kind of shape, like a tilted rectangle stretched into a parallelogram
+    pub fn transformed(&self, transform: &Transform) -> Option<Shape> {
+        let scale = transform.scale;
+        let center = |center: Point| transform.apply(center);
+        // how much the shape's own axes are stretched and where they end up turned
+        let axes = |rotation: f32| {
+            let (sin, cos) = rotation.sin_cos();
+            let uniform =
+                (scale.x.abs() - scale.y.abs()).abs() <= ALIGNED * scale.x.abs().max(scale.y.abs());
+            if !uniform && sin.abs() > ALIGNED && cos.abs() > ALIGNED {
+                return None;
+            }
+            // turned a quarter, the shape's axes are the other way around
+            let stretch = match cos.abs() >= sin.abs() {
+                true => Vector::new(scale.x.abs(), scale.y.abs()),
+                false => Vector::new(scale.y.abs(), scale.x.abs()),
+            };
+            // shapes are symmetric across their own axes, so a flip is a turn
+            let rotation = (sin * scale.y.signum()).atan2(cos * scale.x.signum());
+            Some((stretch, rotation + transform.rotation))
+        };
+        let stretched = |v: Vector, stretch: Vector| Vector::new(v.x * stretch.x, v.y * stretch.y);
+
+        Some(match *self {
+            Shape::Rectangle {
+                center: c,
+                half,
+                rotation,
+            } => {
+                let (stretch, rotation) = axes(rotation)?;
+                Shape::Rectangle {
+                    center: center(c),
+                    half: stretched(half, stretch),
+                    rotation,
+                }
+            }
+            Shape::Ellipse {
+                center: c,
+                radii,
+                rotation,
+            } => {
+                let (stretch, rotation) = axes(rotation)?;
+                Shape::Ellipse {
+                    center: center(c),
+                    radii: stretched(radii, stretch),
+                    rotation,
+                }
+            }
+            Shape::Polygon {
+                center: c,
+                radii,
+                rotation,
+                sides,
+            } => {
+                let (stretch, rotation) = axes(rotation)?;
+                Shape::Polygon {
+                    center: center(c),
+                    radii: stretched(radii, stretch),
+                    rotation,
+                    sides,
+                }
+            }
+            Shape::Arrow { from, to, head } => {
+                let (moved_from, moved_to) = (transform.apply(from), transform.apply(to));
+                let length = from.distance(to);
+                Shape::Arrow {
+                    from: moved_from,
+                    to: moved_to,
+                    head: match length > 0. {
+                        true => head * moved_from.distance(moved_to) / length,
+                        false => head,
+                    },
+                }
+            }
+        })
+    }
+
+    /// The outline as a single polyline, for hit testing and export
+    pub fn flatten(&self) -> Vec<Point> {
+        match *self {
+            Shape::Rectangle {
+                center,
+                half,
+                rotation,
+            } => {
+                let corners = [(-1., -1.), (1., -1.), (1., 1.), (-1., 1.), (-1., -1.)];
+                corners
+                    .iter()
+                    .map(|&(x, y)| center + rotate(Vector::new(half.x * x, half.y * y), rotation))
+                    .collect()
+            }
+            Shape::Ellipse {
+                center,
+                radii,
+                rotation,
+            } => around(center, radii, rotation, ELLIPSE_SEGMENTS),
+            Shape::Polygon {
+                center,
+                radii,
+                rotation,
+                sides,
+            } => around(center, radii, rotation, sides as usize),
+            Shape::Arrow { from, to, head } => {
+                let (left, right) = arrow_head(from, to, head);
+                vec![from, to, left, to, right]
+            }
+        }
+    }
+
+    /// Path to stroke when drawing the shape
+    pub fn path(&self) -> Path {
+        Path::new(|builder| match *self {
+            Shape::Ellipse {
+                center,
+                radii,
+                rotation,
+            } => builder.ellipse(path::arc::Elliptical {
+                center,
+                radii,
+                rotation: Radians(rotation),
+                start_angle: Radians(0.),
+                end_angle: Radians(TAU),
+            }),
+            Shape::Arrow { from, to, head } => {
+                let (left, right) = arrow_head(from, to, head);
+                builder.move_to(from);
+                builder.line_to(to);
+                builder.move_to(left);
+                builder.line_to(to);
+                builder.line_to(right);
+            }
+            Shape::Rectangle { .. } | Shape::Polygon { .. } => {
+                let points = self.flatten();
+                builder.move_to(points[0]);
+                points[1..].iter().for_each(|&p| builder.line_to(p));
+                builder.close();
+            }
+        })
+    }
+}
+
+/// `count` points evenly spaced around an ellipse, closed by repeating the first
+fn around(center: Point, radii: Vector, rotation: f32, count: usize) -> Vec<Point> {
+    (0..=count)
+        .map(|i| {
+            let angle = TAU * (i % count) as f32 / count as f32;
+            center
+                + rotate(
+                    Vector::new(radii.x * angle.cos(), radii.y * angle.sin()),
+                    rotation,
+                )
+        })
+        .collect()
+}
+
+/// The two barbs of an arrow head at `to`
+fn arrow_head(from: Point, to: Point, head: f32) -> (Point, Point) {
+    let delta = from - to;
+    let angle = delta.y.atan2(delta.x);
+    let barb =
+        |offset: f32| to + Vector::new((angle + offset).cos(), (angle + offset).sin()) * head;
+    (barb(FRAC_PI_4 / 1.5), barb(-FRAC_PI_4 / 1.5))
+}
+
+pub fn rotate(v: Vector, angle: f32) -> Vector {
+    let (sin, cos) = angle.sin_cos();
+    Vector::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+/// Rotate `end` around `start` to the nearest multiple of `step` radians
+pub fn snap_angle(start: Point, end: Point, step: f32) -> Point {
+    let delta = end - start;
+    let angle = (delta.y.atan2(delta.x) / step).round() * step;
+    let length = delta.x.hypot(delta.y);
+    start + Vector::new(angle.cos(), angle.sin()) * length
+}
@@ -0,0 +1,216 @@
+use std::collections::VecDeque;
+use std::fmt;
+
+use iced::Point;
+
+/// Largest moving average window, at full strength
+const MAX_WINDOW: f32 = 16.;
+/// Length of the lazy nib's string at full strength, in world units
+const MAX_STRING: f32 = 40.;
+
+/// How pen input is smoothed before it's added to a stroke
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StabilizerMode {
+    #[default]
+    Off,
+    /// Average of the last few inputs
+    MovingAverage,
+    /// Each input pulls the pen part of the way towards it
+    Exponential,
+    /// The pen trails the cursor on a string and only moves once it's pulled taut
+    LazyNib,
+}
+
+impl StabilizerMode {
+    pub const ALL: [StabilizerMode; 4] = [
+        StabilizerMode::Off,
+        StabilizerMode::MovingAverage,
+        StabilizerMode::Exponential,
+        StabilizerMode::LazyNib,
+    ];
+}
+
+impl fmt::Display for StabilizerMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            StabilizerMode::Off => "No stabilizer",
+            StabilizerMode::MovingAverage => "Moving average",
+            StabilizerMode::Exponential => "Exponential",
+            StabilizerMode::LazyNib => "Lazy nib",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Stabilizer {
+    pub mode: StabilizerMode,
+    /// How strongly input is smoothed, in `0.0..=1.0`
+    pub strength: f32,
+    /// Recent inputs, for the moving average
+    history: VecDeque<Point>,
+    /// Stabilized pen position
+    position: Option<Point>,
+    /// Last raw input
+    cursor: Option<Point>,
+}
+
+impl Stabilizer {
+    pub fn new(mode: StabilizerMode, strength: f32) -> Self {
+        Stabilizer {
+            mode,
+            strength,
+            ..Stabilizer::default()
+        }
+    }
+
+    /// Forget the previous stroke
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.position = None;
+        self.cursor = None;
+    }
+
+    /// Feed a raw input point, returns the stabilized pen position if the pen moved
+    pub fn stabilize(&mut self, input: Point) -> Option<Point> {
+        self.cursor = Some(input);
+        let Some(position) = self.position else {
+            self.history.push_back(input);
+            self.position = Some(input);
+            return self.position;
+        };
+
+        let next = match self.mode {
+            StabilizerMode::Off => input,
+            StabilizerMode::MovingAverage => {
+                let window = 1 + (self.strength * (MAX_WINDOW - 1.)).round() as usize;
+                self.history.push_back(input);
+                while self.history.len() > window {
+                    self.history.pop_front();
+                }
+                let count = self.history.len() as f32;
+                let (x, y) = self
+                    .history
+                    .iter()
+                    .fold((0., 0.), |(x, y), p| (x + p.x, y + p.y));
+                Point::new(x / count, y / count)
+            }
+            StabilizerMode::Exponential => {
+                // never fully stop following, or the pen would freeze at full strength
+                let follow = 1. - self.strength * 0.95;
+                position + (input - position) * follow
+            }
+            StabilizerMode::LazyNib => {
+                let string = self.strength * MAX_STRING;
+                let distance = position.distance(input);
+                if distance <= string {
+                    return None;
+                }
+                position + (input - position) * ((distance - string) / distance)
+            }
+        };
+
+        self.position = Some(next);
+        Some(next)
+    }
+
+    /// Move the pen to where the cursor was lifted, returns that point if the pen was
+    /// lagging behind it
+    pub fn finish(&mut self) -> Option<Point> {
+        let cursor = self
+            .cursor
+            .filter(|&cursor| self.position != Some(cursor))?;
+        self.history.clear();
+        self.position = Some(cursor);
+        Some(cursor)
+    }
+
+    /// The stabilized pen position and the raw cursor it follows, while they differ
+    pub fn guide(&self) -> Option<(Point, Point)> {
+        match (self.mode, self.position, self.cursor) {
+            (StabilizerMode::Off, _, _) => None,
+            (_, Some(position), Some(cursor)) if position != cursor => Some((position, cursor)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Inputs along the x axis, `step` apart
+    fn line(count: usize, step: f32) -> impl Iterator<Item = Point> {
+        (0..count).map(move |i| Point::new(i as f32 * step, 0.))
+    }
+
+    #[test]
+    fn off_follows_the_input() {
+        let mut stabilizer = Stabilizer::new(StabilizerMode::Off, 1.);
+        for input in line(10, 3.) {
+            assert_eq!(stabilizer.stabilize(input), Some(input));
+        }
+        assert_eq!(stabilizer.guide(), None);
+        assert_eq!(stabilizer.finish(), None);
+    }
+
+    #[test]
+    fn moving_average_of_the_window() {
+        // a window of 4 inputs
+        let mut stabilizer = Stabilizer::new(StabilizerMode::MovingAverage, 0.2);
+        let positions: Vec<_> = line(6, 4.)
+            .map(|input| stabilizer.stabilize(input).expect("a position"))
+            .collect();
+        assert_eq!(positions[0], Point::ORIGIN);
+        assert_eq!(positions[1], Point::new(2., 0.));
+        assert_eq!(positions[5], Point::new(14., 0.));
+        assert_eq!(stabilizer.finish(), Some(Point::new(20., 0.)));
+        assert_eq!(stabilizer.guide(), None);
+    }
+
+    #[test]
+    fn exponential_closes_in() {
+        let mut stabilizer = Stabilizer::new(StabilizerMode::Exponential, 1.);
+        stabilizer.stabilize(Point::ORIGIN);
+        let target = Point::new(100., 0.);
+        let first = stabilizer.stabilize(target).expect("a position");
+        // a twentieth of the way at full strength
+        assert!((first.x - 5.).abs() < 1e-4 && first.y == 0.);
+        let mut last = first;
+        for _ in 0..100 {
+            let next = stabilizer.stabilize(target).expect("a position");
+            assert!(next.x > last.x && next.x < target.x);
+            last = next;
+        }
+        assert_eq!(stabilizer.finish(), Some(target));
+    }
+
+    #[test]
+    fn lazy_nib_waits_for_the_string() {
+        // a string 20 long
+        let mut stabilizer = Stabilizer::new(StabilizerMode::LazyNib, 0.5);
+        assert_eq!(stabilizer.stabilize(Point::ORIGIN), Some(Point::ORIGIN));
+        assert_eq!(stabilizer.stabilize(Point::new(15., 0.)), None);
+        assert_eq!(
+            stabilizer.stabilize(Point::new(30., 0.)),
+            Some(Point::new(10., 0.))
+        );
+        assert_eq!(
+            stabilizer.guide(),
+            Some((Point::new(10., 0.), Point::new(30., 0.)))
+        );
+        // lifting the pen draws the rest of the way
+        assert_eq!(stabilizer.finish(), Some(Point::new(30., 0.)));
+        assert_eq!(stabilizer.finish(), None);
+    }
+
+    #[test]
+    fn reset_forgets_the_stroke() {
+        let mut stabilizer = Stabilizer::new(StabilizerMode::LazyNib, 1.);
+        stabilizer.stabilize(Point::ORIGIN);
+        stabilizer.stabilize(Point::new(10., 0.));
+        stabilizer.reset();
+        assert_eq!(stabilizer.finish(), None);
+        let start = Point::new(50., 50.);
+        assert_eq!(stabilizer.stabilize(start), Some(start));
+    }
+}
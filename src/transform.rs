@@ -0,0 +1,185 @@
+//! Moving, scaling and rotating curves
+//!
+//! Selected curves get a box around them with handles to drag. Dragging a handle gives
+//! a [`Transform`] that is applied to the geometry of the curves, so shapes and fitted
+//! curves stay exact where they can.
+use iced::{Point, Rectangle, Vector};
+
+use crate::geometry as geo;
+use crate::shape::{rotate, SNAP_ANGLE};
+
+/// Scaling by `scale` away from `origin`, then rotating by `rotation` around it and
+/// moving by `offset`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    pub origin: Point,
+    /// Negative on an axis that is flipped
+    pub scale: Vector,
+    pub rotation: f32,
+    pub offset: Vector,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            origin: Point::ORIGIN,
+            scale: Vector::new(1., 1.),
+            rotation: 0.,
+            offset: Vector::ZERO,
+        }
+    }
+}
+
+impl Transform {
+    pub fn apply(&self, point: Point) -> Point {
+        let from = point - self.origin;
+        let scaled = Vector::new(from.x * self.scale.x, from.y * self.scale.y);
+        self.origin + rotate(scaled, self.rotation) + self.offset
+    }
+
+    /// Whether it leaves everything where it is
+    pub fn is_identity(&self) -> bool {
+        *self
+            == Transform {
+                origin: self.origin,
+                ..Transform::default()
+            }
+    }
+}
+
+/// Part of a transform box that can be dragged
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Handle {
+    /// Anywhere inside the box
+    Move,
+    /// A corner or the middle of an edge, `-1`, `0` or `1` on each axis from the center
+    Scale(Vector),
+    /// Above the top edge
+    Rotate,
+}
+
+/// The box around selected curves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransformBox {
+    pub bounds: Rectangle,
+}
+
+impl TransformBox {
+    pub fn new(bounds: Rectangle) -> Self {
+        TransformBox { bounds }
+    }
+
+    /// Corners, clockwise from the top left
+    pub fn corners(&self) -> [Point; 4] {
+        let Rectangle {
+            x,
+            y,
+            width,
+            height,
+        } = self.bounds;
+        [
+            Point::new(x, y),
+            Point::new(x + width, y),
+            Point::new(x + width, y + height),
+            Point::new(x, y + height),
+        ]
+    }
+
+    /// Handles and where they are, the rotation handle `stem` above the top edge
+    pub fn handles(&self, stem: f32) -> Vec<(Handle, Point)> {
+        let center = self.bounds.center();
+        let half = Vector::new(self.bounds.width / 2., self.bounds.height / 2.);
+        let sides = [
+            (-1., -1.),
+            (0., -1.),
+            (1., -1.),
+            (1., 0.),
+            (1., 1.),
+            (0., 1.),
+            (-1., 1.),
+            (-1., 0.),
+        ];
+        sides
+            .into_iter()
+            .map(|(x, y)| {
+                let position = center + Vector::new(half.x * x, half.y * y);
+                (Handle::Scale(Vector::new(x, y)), position)
+            })
+            .chain([(Handle::Rotate, Point::new(center.x, self.bounds.y - stem))])
+            .collect()
+    }
+
+    /// The handle within `radius` of `point`, or moving the box when it's inside
+    pub fn hit(&self, point: Point, radius: f32, stem: f32) -> Option<Handle> {
+        self.handles(stem)
+            .into_iter()
+            .map(|(handle, position)| (handle, position.distance(point)))
+            .filter(|(_, distance)| *distance <= radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(handle, _)| handle)
+            .or_else(|| geo::contains(&self.bounds, point).then_some(Handle::Move))
+    }
+
+    /// What dragging `handle` from `start` to `end` does. When `constrain`ed, moves
+    /// stay horizontal or vertical, scaling keeps the aspect ratio and rotation snaps
+    /// to 15°.
+    pub fn drag(&self, handle: Handle, start: Point, end: Point, constrain: bool) -> Transform {
+        let center = self.bounds.center();
+        let delta = end - start;
+        match handle {
+            Handle::Move => {
+                let offset = match constrain {
+                    true if delta.x.abs() >= delta.y.abs() => Vector::new(delta.x, 0.),
+                    true => Vector::new(0., delta.y),
+                    false => delta,
+                };
+                Transform {
+                    offset,
+                    ..Transform::default()
+                }
+            }
+            Handle::Scale(side) => {
+                let half = Vector::new(
+                    side.x * self.bounds.width / 2.,
+                    side.y * self.bounds.height / 2.,
+                );
+                // the opposite side stays where it is
+                let anchor = center - half;
+                let ratio = |moved: f32, size: f32| match size.abs() > f32::EPSILON {
+                    true => (size + moved) / size,
+                    false => 1.,
+                };
+                let mut scale =
+                    Vector::new(ratio(delta.x, half.x * 2.), ratio(delta.y, half.y * 2.));
+                if constrain {
+                    // the axis dragged along, or the one stretched the most from a corner
+                    let uniform = if side.x == 0. || (side.y != 0. && scale.y.abs() > scale.x.abs())
+                    {
+                        scale.y.abs()
+                    } else {
+                        scale.x.abs()
+                    };
+                    scale = Vector::new(uniform.copysign(scale.x), uniform.copysign(scale.y));
+                }
+                Transform {
+                    origin: anchor,
+                    scale,
+                    ..Transform::default()
+                }
+            }
+            Handle::Rotate => {
+                let angle = |point: Point| (point.y - center.y).atan2(point.x - center.x);
+                let rotation = angle(end) - angle(start);
+                let rotation = match constrain {
+                    true => (rotation / SNAP_ANGLE).round() * SNAP_ANGLE,
+                    false => rotation,
+                };
+                Transform {
+                    origin: center,
+                    rotation,
+                    ..Transform::default()
+                }
+            }
+        }
+    }
+}
@@ -1 +1,2 @@
+pub mod held;
 pub mod workspace;
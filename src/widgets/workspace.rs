@@ -1,16 +1,23 @@
 use glam::Vec3;
+use iced::advanced::clipboard::{self, Clipboard};
 use iced::advanced::layout::{self, Layout};
 use iced::advanced::widget::{tree, Tree};
-use iced::advanced::{Clipboard, Shell, Widget};
+use iced::advanced::{Shell, Widget};
 use iced::mouse::Event::{ButtonPressed, ButtonReleased, CursorMoved, WheelScrolled};
 use iced::mouse::ScrollDelta;
 use iced::touch::Event::{FingerLifted, FingerLost, FingerMoved, FingerPressed};
-use iced::widget::canvas::Cache;
+use iced::widget::canvas::{Cache, Frame, LineDash, Path, Stroke};
 use iced::{event, keyboard, mouse, Color, Point, Theme, Vector};
 use iced::{Element, Event};
 use iced::{Length, Rectangle, Size};
 
-use crate::curve::Curve;
+use crate::curve::{Curve, Paint};
+use crate::transform::Handle;
+
+/// Color selected curves are outlined with
+const SELECTION: Color = Color::from_rgba(0.45, 0.75, 1., 0.9);
+/// Half the side of the square scale handles of a transform box
+const HANDLE_SIZE: f32 = 4.;
 
 /// A workspace is a an infinite canvas that can be zoomed, panned,
 /// and contains widgets that can be placed anywhere in 3d (stacking in Z)
@@ -21,15 +28,32 @@ where
 {
     camera: &'a Vec3,
     elements: Vec<(Point, Element<'a, Message, Theme, Renderer>)>,
-    primitives: &'a Vec<Curve>,
+    /// Curves bottom first, with the opacity to draw them at
+    primitives: Vec<(&'a Curve, f32)>,
     cache: &'a Cache<Renderer>,
+    live: Option<(&'a Curve, f32, &'a Cache<Renderer>)>,
+    guide: Option<(Point, Point)>,
+    region: Option<Rectangle>,
+    selection: Option<(Vec<&'a Curve>, &'a Cache<Renderer>)>,
+    lasso: &'a [Point],
+    transform_box: Option<TransformOutline>,
     pan: Option<Box<dyn Fn(Vector) -> Message + 'a>>,
     zoom: Option<Box<dyn Fn(f32) -> Message + 'a>>,
     on_press: Option<Box<dyn Fn(Point) -> Message + 'a>>,
     on_move: Option<Box<dyn Fn(Point) -> Message + 'a>>,
     on_release: Option<Box<dyn Fn(Point) -> Message + 'a>>,
+    on_modifiers: Option<Box<dyn Fn(keyboard::Modifiers) -> Message + 'a>>,
+    on_key: Option<OnKey<'a, Message>>,
+    on_copy: Option<Box<dyn Fn() -> Option<String> + 'a>>,
+    on_cut: Option<Message>,
+    on_paste: Option<Box<dyn Fn(String, Point) -> Message + 'a>>,
 }
 
+/// Outline of a transform box and its handles
+type TransformOutline = ([Point; 4], Vec<(Handle, Point)>);
+
+type OnKey<'a, Message> = Box<dyn Fn(&keyboard::Key, keyboard::Modifiers) -> Option<Message> + 'a>;
+
 #[derive(Debug, Clone, PartialEq, Default)]
 struct InnerState {
     modifiers: keyboard::Modifiers,
@@ -42,7 +66,7 @@ where
 {
     pub fn new(
         camera: &'a Vec3,
-        primitives: &'a Vec<Curve>,
+        primitives: Vec<(&'a Curve, f32)>,
         elements: Vec<(Point, Element<'a, Message, Theme, Renderer>)>,
         cache: &'a Cache<Renderer>,
     ) -> Self {
@@ -51,14 +75,65 @@ where
             primitives,
             elements,
             cache,
+            live: None,
+            guide: None,
+            region: None,
+            selection: None,
+            lasso: &[],
+            transform_box: None,
             pan: None,
             zoom: None,
             on_press: None,
             on_move: None,
             on_release: None,
+            on_modifiers: None,
+            on_key: None,
+            on_copy: None,
+            on_cut: None,
+            on_paste: None,
         }
     }
 
+    /// A curve that is still being drawn, cached separately so the
+    /// finished primitives don't have to be redrawn while it changes
+    pub fn live(mut self, curve: &'a Curve, opacity: f32, cache: &'a Cache<Renderer>) -> Self {
+        self.live = Some((curve, opacity, cache));
+        self
+    }
+
+    /// Show where the pen is (in world coordinates) relative to the cursor,
+    /// for stabilizers where the two drift apart
+    pub fn guide(mut self, pen: Point, cursor: Point) -> Self {
+        self.guide = Some((pen, cursor));
+        self
+    }
+
+    /// Outline an area of the canvas (in world coordinates), like the region to export
+    pub fn region(mut self, region: Rectangle) -> Self {
+        self.region = Some(region);
+        self
+    }
+
+    /// Outline the selected curves, cached separately so selecting doesn't redraw
+    /// all curves
+    pub fn selection(mut self, curves: Vec<&'a Curve>, cache: &'a Cache<Renderer>) -> Self {
+        self.selection = Some((curves, cache));
+        self
+    }
+
+    /// The loop of a lasso or marquee being drawn (in world coordinates)
+    pub fn lasso(mut self, points: &'a [Point]) -> Self {
+        self.lasso = points;
+        self
+    }
+
+    /// Outline of a transform box (in world coordinates), clockwise from the top left,
+    /// with the handles to drag it by
+    pub fn transform_box(mut self, outline: [Point; 4], handles: Vec<(Handle, Point)>) -> Self {
+        self.transform_box = Some((outline, handles));
+        self
+    }
+
     pub fn pan(mut self, pan: impl Fn(Vector) -> Message + 'a) -> Self {
         self.pan = Some(Box::new(pan));
         self
@@ -83,6 +158,42 @@ where
         self.on_release = Some(Box::new(on_release));
         self
     }
+
+    pub fn on_modifiers(
+        mut self,
+        on_modifiers: impl Fn(keyboard::Modifiers) -> Message + 'a,
+    ) -> Self {
+        self.on_modifiers = Some(Box::new(on_modifiers));
+        self
+    }
+
+    /// Keyboard shortcuts, the key press is captured when a message is returned
+    pub fn on_key(
+        mut self,
+        on_key: impl Fn(&keyboard::Key, keyboard::Modifiers) -> Option<Message> + 'a,
+    ) -> Self {
+        self.on_key = Some(Box::new(on_key));
+        self
+    }
+
+    /// Text to put on the clipboard on Ctrl+C and Ctrl+X, `None` if there's nothing
+    /// to copy. Only called when copying.
+    pub fn on_copy(mut self, on_copy: impl Fn() -> Option<String> + 'a) -> Self {
+        self.on_copy = Some(Box::new(on_copy));
+        self
+    }
+
+    /// Published after Ctrl+X copied something, to remove it
+    pub fn on_cut(mut self, on_cut: Message) -> Self {
+        self.on_cut = Some(on_cut);
+        self
+    }
+
+    /// Text on the clipboard on Ctrl+V, and where the cursor is
+    pub fn on_paste(mut self, on_paste: impl Fn(String, Point) -> Message + 'a) -> Self {
+        self.on_paste = Some(Box::new(on_paste));
+        self
+    }
 }
 
 /// Implement Widet
@@ -151,11 +262,128 @@ where
                 frame.translate(Vector::new(-self.camera.x, -self.camera.y));
 
                 //// Foreground
-                self.primitives.iter().for_each(|v| v.draw(frame));
+                self.primitives
+                    .iter()
+                    .for_each(|(v, opacity)| v.draw_with_opacity(frame, *opacity));
             });
 
         renderer.draw_geometry(geo);
 
+        //// Curve being drawn
+        if let Some((curve, opacity, cache)) = self.live {
+            let geo = cache.draw(renderer, workspace_layout.bounds().size() * 2., |frame| {
+                frame.translate(Vector::new(-self.camera.x, -self.camera.y));
+                curve.draw_with_opacity(frame, opacity);
+            });
+            renderer.draw_geometry(geo);
+        }
+
+        //// Selection outlines
+        if let Some((curves, cache)) = &self.selection {
+            let geo = cache.draw(renderer, workspace_layout.bounds().size() * 2., |frame| {
+                frame.translate(Vector::new(-self.camera.x, -self.camera.y));
+                let stroke = Stroke::default().with_color(SELECTION).with_width(1.5);
+                for curve in curves {
+                    match curve.paint() {
+                        Paint::Fill(paths) => paths
+                            .into_iter()
+                            .for_each(|path| frame.stroke(path, stroke)),
+                        Paint::Stroke { path, .. } => frame.stroke(path, stroke),
+                    }
+                }
+            });
+            renderer.draw_geometry(geo);
+        }
+
+        //// Lasso, changes with every move so it's not cached
+        if let [first, rest @ ..] = self.lasso {
+            let mut frame = Frame::new(renderer, workspace_layout.bounds().size());
+            frame.translate(Vector::new(-self.camera.x, -self.camera.y));
+            let lasso = Path::new(|builder| {
+                builder.move_to(*first);
+                rest.iter().for_each(|point| builder.line_to(*point));
+                builder.close();
+            });
+            frame.fill(&lasso, SELECTION.scale_alpha(0.1));
+            frame.stroke(
+                &lasso,
+                Stroke {
+                    line_dash: LineDash {
+                        segments: &[4., 4.],
+                        offset: 0,
+                    },
+                    ..Stroke::default().with_color(SELECTION).with_width(1.)
+                },
+            );
+            renderer.draw_geometry(frame.into_geometry());
+        }
+
+        //// Transform box, changes with every move while it's dragged so it's not cached
+        if let Some((outline, handles)) = &self.transform_box {
+            let mut frame = Frame::new(renderer, workspace_layout.bounds().size());
+            frame.translate(Vector::new(-self.camera.x, -self.camera.y));
+            let stroke = Stroke::default().with_color(SELECTION).with_width(1.);
+            frame.stroke(
+                &Path::new(|builder| {
+                    builder.move_to(outline[0]);
+                    outline[1..]
+                        .iter()
+                        .for_each(|point| builder.line_to(*point));
+                    builder.close();
+                }),
+                stroke,
+            );
+            let top = outline[0] + (outline[1] - outline[0]) * 0.5;
+            for &(handle, position) in handles {
+                let path = match handle {
+                    Handle::Move => continue,
+                    Handle::Scale(_) => Path::rectangle(
+                        position - Vector::new(HANDLE_SIZE, HANDLE_SIZE),
+                        Size::new(HANDLE_SIZE * 2., HANDLE_SIZE * 2.),
+                    ),
+                    Handle::Rotate => {
+                        frame.stroke(&Path::line(top, position), stroke);
+                        Path::circle(position, HANDLE_SIZE)
+                    }
+                };
+                frame.fill(&path, Color::WHITE);
+                frame.stroke(&path, stroke);
+            }
+            renderer.draw_geometry(frame.into_geometry());
+        }
+
+        //// Stabilizer guide, changes with every move so it's not cached
+        if let Some((pen, cursor)) = self.guide {
+            let mut frame = Frame::new(renderer, workspace_layout.bounds().size());
+            frame.translate(Vector::new(-self.camera.x, -self.camera.y));
+            let color = Color::from_rgba(1., 1., 1., 0.4);
+            frame.stroke(
+                &Path::line(pen, cursor),
+                Stroke::default().with_color(color).with_width(1.),
+            );
+            frame.fill(&Path::circle(pen, 3.), color);
+            renderer.draw_geometry(frame.into_geometry());
+        }
+
+        //// Region outline
+        if let Some(region) = self.region {
+            let mut frame = Frame::new(renderer, workspace_layout.bounds().size());
+            frame.translate(Vector::new(-self.camera.x, -self.camera.y));
+            frame.stroke(
+                &Path::rectangle(region.position(), region.size()),
+                Stroke {
+                    line_dash: LineDash {
+                        segments: &[6., 4.],
+                        offset: 0,
+                    },
+                    ..Stroke::default()
+                        .with_color(Color::from_rgba(1., 1., 1., 0.6))
+                        .with_width(1.)
+                },
+            );
+            renderer.draw_geometry(frame.into_geometry());
+        }
+
         let padding = 0.0;
 
         //// Render Children in a layer that is bounded to the size of the workspace
@@ -185,7 +413,59 @@ where
         // update inner state
         let inner_state = tree::State::downcast_mut::<InnerState>(&mut tree.state);
         if let Event::Keyboard(keyboard::Event::ModifiersChanged(modifiers)) = event {
-            inner_state.modifiers = modifiers
+            inner_state.modifiers = modifiers;
+            if let Some(on_modifiers) = &self.on_modifiers {
+                shell.publish(on_modifiers(modifiers));
+            }
+        }
+
+        if let Event::Keyboard(keyboard::Event::KeyPressed {
+            key: keyboard::Key::Character(character),
+            modifiers,
+            ..
+        }) = &event
+        {
+            let command = modifiers.command();
+            match character.to_lowercase().as_str() {
+                // without a way to remove what was copied there's nothing to cut
+                "x" if command && self.on_cut.is_none() => return event::Status::Captured,
+                "c" | "x" if command && self.on_copy.is_some() => {
+                    let copied = self.on_copy.as_ref().and_then(|on_copy| on_copy());
+                    if let Some(text) = copied {
+                        clipboard.write(clipboard::Kind::Standard, text);
+                        if character.eq_ignore_ascii_case("x") {
+                            if let Some(on_cut) = &self.on_cut {
+                                shell.publish(on_cut.clone());
+                            }
+                        }
+                    }
+                    return event::Status::Captured;
+                }
+                "v" if command => {
+                    if let (Some(on_paste), Some(text)) =
+                        (&self.on_paste, clipboard.read(clipboard::Kind::Standard))
+                    {
+                        // paste in the middle of the workspace when the cursor is elsewhere
+                        let at = cursor
+                            .position_over(layout.bounds())
+                            .unwrap_or(layout.bounds().center());
+                        shell.publish(on_paste(text, at));
+                        return event::Status::Captured;
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Event::Keyboard(keyboard::Event::KeyPressed { key, modifiers, .. }) = &event {
+            if let Some(message) = self
+                .on_key
+                .as_ref()
+                .and_then(|on_key| on_key(key, *modifiers))
+            {
+                shell.publish(message);
+                return event::Status::Captured;
+            }
         }
 
         ////Pass event down to children
@@ -306,7 +586,7 @@ where
 /// Create a new `Workspace`
 pub fn workspace<'a, Message, Theme, Renderer>(
     camera: &'a glam::Vec3,
-    primitives: &'a Vec<Curve>,
+    primitives: Vec<(&'a Curve, f32)>,
     elements: Vec<(Point, Element<'a, Message, Theme, Renderer>)>,
     cache: &'a Cache<Renderer>,
 ) -> Workspace<'a, Message, Theme, Renderer>
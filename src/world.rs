@@ -1,21 +1,45 @@
-use crate::curve::Curve;
+use crate::clip;
+use crate::curve::{self, Curve, Sample, DEFAULT_PRESSURE, DEFAULT_WIDTH};
+use crate::document::{self, Document};
+use crate::edit::Splice;
+use crate::export::{pdf, png, svg};
+use crate::history::{Change, History, LayerChange};
+use crate::import;
+use crate::journal::Journal;
+use crate::layer::{LayerId, Layers};
+use crate::playback::Playback;
+use crate::shape::{snap_angle, Shape, ShapeKind, SNAP_ANGLE};
+use crate::stabilizer::{Stabilizer, StabilizerMode};
+use crate::transform::{Handle, Transform, TransformBox};
+use crate::widgets::held::Held;
 use crate::widgets::workspace;
 use glam::Vec3;
 use iced::widget::canvas::Cache;
 use iced::widget::{
-    button, column, container, horizontal_space, radio, row, stack, vertical_space,
+    button, checkbox, column, container, horizontal_space, pick_list, radio, row, scrollable,
+    slider, stack, text, text_input, vertical_space, Space,
 };
-use iced::{Alignment, Length, Vector};
+use iced::{keyboard, Alignment, Length, Rectangle, Size, Vector};
 use iced::{Color, Element, Point, Theme};
 use iced_wgpu::Renderer;
 use iced_winit::runtime::{Program, Task};
+use std::io;
+use std::path::PathBuf;
+use std::time::Instant;
 use style::color_button;
 
 #[derive(Debug, Clone, PartialEq, Eq, Copy)]
 pub enum Tool {
-    Line,
+    Line(bool),
+    Shape(ShapeKind, bool),
     Pen(bool),
     Erase(bool),
+    /// Drag out the region to export
+    Region(bool),
+    /// Draw a loop around the curves to select
+    Lasso(bool),
+    /// Drag a rectangle around the curves to select
+    Marquee(bool),
 }
 
 /// unit version of Tool, used for initialize Tool and displaying summarized version of Tool
@@ -23,28 +47,130 @@ pub enum Tool {
 pub enum SelectedTool {
     Pen,
     Line,
+    Shape(ShapeKind),
     Erase,
+    Region,
+    Lasso,
+    Marquee,
 }
 
 impl From<SelectedTool> for Tool {
     fn from(val: SelectedTool) -> Self {
         match val {
-            SelectedTool::Line => Tool::Line,
+            SelectedTool::Line => Tool::Line(false),
+            SelectedTool::Shape(kind) => Tool::Shape(kind, false),
             SelectedTool::Pen => Tool::Pen(false),
             SelectedTool::Erase => Tool::Erase(false),
+            SelectedTool::Region => Tool::Region(false),
+            SelectedTool::Lasso => Tool::Lasso(false),
+            SelectedTool::Marquee => Tool::Marquee(false),
         }
     }
 }
 impl From<Tool> for SelectedTool {
     fn from(val: Tool) -> Self {
         match val {
-            Tool::Line => SelectedTool::Line,
+            Tool::Line(_) => SelectedTool::Line,
+            Tool::Shape(kind, _) => SelectedTool::Shape(kind),
             Tool::Pen(_) => SelectedTool::Pen,
             Tool::Erase(_) => SelectedTool::Erase,
+            Tool::Region(_) => SelectedTool::Region,
+            Tool::Lasso(_) => SelectedTool::Lasso,
+            Tool::Marquee(_) => SelectedTool::Marquee,
+        }
+    }
+}
+
+/// What the eraser removes from the curves it touches
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Default)]
+pub enum EraseMode {
+    /// Whole strokes
+    #[default]
+    Stroke,
+    /// Only the part under the eraser, splitting the stroke
+    Partial,
+}
+
+impl EraseMode {
+    pub const ALL: [EraseMode; 2] = [EraseMode::Stroke, EraseMode::Partial];
+}
+
+impl std::fmt::Display for EraseMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            EraseMode::Stroke => "Erase strokes",
+            EraseMode::Partial => "Erase partially",
+        })
+    }
+}
+
+/// Which curves a lasso or marquee selects
+#[derive(Debug, Clone, PartialEq, Eq, Copy, Default)]
+pub enum SelectMode {
+    /// Curves entirely inside
+    #[default]
+    Inside,
+    /// Curves with any part inside
+    Touching,
+}
+
+impl SelectMode {
+    pub const ALL: [SelectMode; 2] = [SelectMode::Inside, SelectMode::Touching];
+}
+
+impl std::fmt::Display for SelectMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SelectMode::Inside => "Select inside",
+            SelectMode::Touching => "Select touching",
+        })
+    }
+}
+
+/// Selected curves shown changed during a drag, they're only edited once it ends
+#[derive(Debug)]
+struct PreviewDrag {
+    /// Indices of the curves being changed, with the curves as they were
+    curves: Vec<(usize, Curve)>,
+}
+
+impl PreviewDrag {
+    fn new(selection: &[usize], curves: &[Curve]) -> Self {
+        PreviewDrag {
+            curves: selection
+                .iter()
+                .map(|&index| (index, curves[index].clone()))
+                .collect(),
+        }
+    }
+
+    /// Show each curve as `change` makes it from how it was
+    fn show(&self, curves: &mut [Curve], change: impl Fn(&Curve) -> Curve) {
+        for (index, curve) in &self.curves {
+            curves[*index] = change(curve);
         }
     }
 }
 
+/// A handle of the transform box being dragged
+#[derive(Debug)]
+struct TransformDrag {
+    handle: Handle,
+    /// The box as it was when the drag started
+    frame: TransformBox,
+    start: Point,
+    preview: PreviewDrag,
+    transform: Transform,
+}
+
+/// Selected curves being restyled by dragging a style slider
+#[derive(Debug)]
+struct RestyleDrag {
+    preview: PreviewDrag,
+    /// Whether the slider moved at all
+    changed: bool,
+}
+
 #[derive(Debug, Clone)]
 pub enum Message {
     Pan(Vector),
@@ -55,28 +181,198 @@ pub enum Message {
     DemoMessage,
     SetTool(SelectedTool),
     SetColor(Color),
+    SetStrokeWidth(f32),
+    /// Alpha of the stroke color
+    SetStrokeOpacity(f32),
+    /// A style slider was pressed, starting a drag that restyles the selection
+    StartRestyle,
+    /// A style slider was let go, restyling the selection as a single step
+    FinishRestyle,
+    /// Pen pressure reported by the input device, applied to the following samples
+    Pressure(f32),
+    SetTolerance(f32),
+    SetFitCurves(bool),
+    SetStabilizer(StabilizerMode),
+    SetStabilizerStrength(f32),
+    SetEraserRadius(f32),
+    SetEraseMode(EraseMode),
+    SetPolygonSides(u32),
+    SetSelectMode(SelectMode),
+    DeleteSelection,
+    Deselect,
+    Modifiers(keyboard::Modifiers),
+    Open,
+    Save,
+    SaveAs,
+    ExportSvg,
+    ExportPng,
+    ExportPdf,
+    SetPngScale(f32),
+    SetPdfLayout(pdf::Layout),
+    SetExportBackground(bool),
+    ImportSvg,
+    /// Text from the clipboard, to paste centered at a point on screen
+    Paste(String, Point),
+    /// Export everything again instead of the dragged out region
+    ClearRegion,
     Clear,
+    Undo,
+    Redo,
+    ShowHistory(bool),
+    /// Go to a node of the history tree
+    JumpTo(usize),
+    /// Start playing back how the drawing was made, or resume
+    Play,
+    Pause,
+    StopPlayback,
+    /// Jump to seconds into playback
+    Seek(f32),
+    SetPlaybackSpeed(f32),
+    /// A frame is due while animating
+    Tick(Instant),
+    /// Add a layer above the active one
+    AddLayer,
+    /// Delete the active layer and its curves
+    DeleteLayer,
+    SelectLayer(LayerId),
+    RenameLayer(String),
+    /// The new name of the active layer was entered
+    FinishRename,
+    ShowLayer(LayerId, bool),
+    LockLayer(LayerId, bool),
+    SetLayerOpacity(f32),
+    RaiseLayer,
+    LowerLayer,
+    /// Restore the canvas recovered from a session that didn't shut down cleanly
+    Recover,
+    DiscardRecovery,
 }
 
 #[derive(Debug)]
 pub struct World {
     curves: Vec<Curve>,
+    layers: Layers,
+    /// The active layer was renamed since the layers were last journaled, the name is
+    /// only journaled once it's entered rather than on every key
+    renamed: bool,
     camera: Vec3,
     pub tool: Tool,
     cache: Cache<iced_wgpu::Renderer>,
+    /// Cache for the curve being drawn, so finished curves aren't redrawn on every move
+    live_cache: Cache<iced_wgpu::Renderer>,
     active_color: Color,
+    /// Width at full pressure of new strokes and shapes
+    stroke_width: f32,
     colors: Vec<Color>, //curve_demo: curve_demo::State,
+    /// Last pressure reported by a pressure sensitive device during this stroke
+    pressure: Option<f32>,
+    /// Max distance in world units at zoom 1 that simplifying a finished stroke may move it
+    tolerance: f32,
+    /// Replace finished strokes with fitted béziers instead of simplifying them
+    fit_curves: bool,
+    /// Smooths pen input while drawing
+    stabilizer: Stabilizer,
+    /// Eraser radius in screen pixels
+    eraser_radius: f32,
+    erase_mode: EraseMode,
+    /// Where the eraser was on the last move, so the whole path between moves is erased
+    erased_to: Option<Point>,
+    /// Where the line, shape, region or marquee being drawn starts
+    drag_start: Option<Point>,
+    /// Area to export instead of all curves, in world coordinates
+    export_region: Option<Rectangle>,
+    /// Pixels per world unit of exported PNGs
+    png_scale: f32,
+    pdf_layout: pdf::Layout,
+    /// Draw the canvas background behind exported PNGs and PDFs
+    export_background: bool,
+    /// Corners of new polygons
+    polygon_sides: u32,
+    /// Loop of the lasso or corners of the marquee being drawn, in world coordinates
+    lasso: Vec<Point>,
+    select_mode: SelectMode,
+    /// Indices of the selected curves in `curves`, in order
+    selection: Vec<usize>,
+    /// Cache for the outlines of the selected curves
+    selection_cache: Cache<iced_wgpu::Renderer>,
+    /// Selected curves being moved, scaled or rotated, they're only edited once the
+    /// drag ends so the whole drag is undone at once
+    transform_drag: Option<TransformDrag>,
+    /// Selected curves being restyled with a slider, they're only edited once the
+    /// slider is let go
+    restyle_drag: Option<RestyleDrag>,
+    modifiers: keyboard::Modifiers,
+    /// Where the document was last opened from or saved to
+    path: Option<PathBuf>,
+    /// Outcome of the last file operation, shown in the tools panel
+    status: Option<String>,
+    /// Edits that can be undone and redone
+    history: History,
+    /// Show the history tree in the tools panel
+    show_history: bool,
+    /// Shown instead of the curves while playing back
+    playback: Option<Playback>,
+    playback_speed: f32,
+    /// When the last frame of an animation was
+    last_tick: Option<Instant>,
+    /// Where edits are logged for crash recovery, if it could be started
+    journal: Option<Journal>,
+    /// Canvas left behind by a session that didn't shut down cleanly, until restored or discarded
+    recovery: Option<Document>,
 }
+const DEFAULT_CAMERA_Z: f32 = 300.;
+/// Distance within which a handle of the transform box is grabbed, in screen pixels
+const HANDLE_RADIUS: f32 = 6.;
+/// Distance of the rotation handle above the transform box, in screen pixels
+const ROTATE_STEM: f32 = 24.;
+/// Closest distance between points of a lasso, in screen pixels
+const LASSO_STEP: f32 = 3.;
+/// Fitting to zero error would put a segment between every pair of samples
+const MIN_FIT_ERROR: f32 = 0.05;
 const L: f32 = 0.2;
 const H: f32 = 0.5;
 
+/// Picker for documents
+fn file_dialog() -> rfd::FileDialog {
+    rfd::FileDialog::new().add_filter("Monstera document", &[document::EXTENSION])
+}
+
+/// Messages for keyboard shortcuts
+fn shortcut(key: &keyboard::Key, modifiers: keyboard::Modifiers) -> Option<Message> {
+    use keyboard::key::Named;
+    match key {
+        keyboard::Key::Named(Named::Delete | Named::Backspace) => Some(Message::DeleteSelection),
+        keyboard::Key::Named(Named::Escape) => Some(Message::Deselect),
+        keyboard::Key::Character(character) => {
+            match (character.to_lowercase().as_str(), modifiers.command()) {
+                ("z", true) if modifiers.shift() => Some(Message::Redo),
+                ("z", true) => Some(Message::Undo),
+                ("y", true) => Some(Message::Redo),
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Ask where to export to
+fn export_dialog(name: &str, extension: &str) -> Option<PathBuf> {
+    rfd::FileDialog::new()
+        .add_filter(name, &[extension])
+        .set_file_name(format!("untitled.{extension}"))
+        .save_file()
+}
+
 impl Default for World {
     fn default() -> Self {
         World {
-            camera: Vec3::new(0., 0., 300.),
+            camera: Vec3::new(0., 0., DEFAULT_CAMERA_Z),
             tool: Tool::Pen(false),
             curves: vec![],
+            layers: Layers::default(),
+            renamed: false,
             cache: Cache::new(),
+            live_cache: Cache::new(),
             colors: vec![
                 Color::from_linear_rgba(0.8, 0.8, 0.8, 1.0),
                 Color::from_linear_rgba(0.5, 0.5, 0.5, 1.0),
@@ -87,6 +383,36 @@ impl Default for World {
                 Color::from_linear_rgba(L, L, H, 1.0),
             ], //curve_demo: curve_demo::State::default(),
             active_color: Color::from_linear_rgba(0.8, 0.8, 0.8, 1.0),
+            stroke_width: DEFAULT_WIDTH,
+            pressure: None,
+            tolerance: 0.5,
+            fit_curves: false,
+            stabilizer: Stabilizer::new(StabilizerMode::Off, 0.5),
+            eraser_radius: 8.,
+            erase_mode: EraseMode::default(),
+            erased_to: None,
+            drag_start: None,
+            export_region: None,
+            png_scale: 2.,
+            pdf_layout: pdf::Layout::default(),
+            export_background: true,
+            polygon_sides: 5,
+            lasso: vec![],
+            select_mode: SelectMode::default(),
+            selection: vec![],
+            selection_cache: Cache::new(),
+            transform_drag: None,
+            restyle_drag: None,
+            modifiers: keyboard::Modifiers::default(),
+            path: None,
+            status: None,
+            history: History::default(),
+            show_history: false,
+            playback: None,
+            playback_speed: 1.,
+            last_tick: None,
+            journal: None,
+            recovery: None,
         }
     }
 }
@@ -96,60 +422,577 @@ impl Program for World {
     type Renderer = Renderer;
 
     fn update(&mut self, message: Message) -> Task<Message> {
+        // journal a new layer name once it is entered, or anything else happens
+        if self.renamed && !matches!(message, Message::RenameLayer(_)) {
+            self.journal_layers();
+        }
+        let edits = message.edits() || (message.restyles() && !self.selection.is_empty());
+        if edits && self.playback.is_some() {
+            self.stop_playback();
+        }
         match message {
             Message::Pan(delta) => {
                 self.camera += Vec3::new(-delta.x * 1.5, -delta.y * 1.5, 0.);
                 self.cache.clear();
+                self.live_cache.clear();
+                self.selection_cache.clear();
             }
 
             Message::Zoom(delta) => {
                 self.camera += Vec3::new(0., 0., delta);
                 self.cache.clear();
+                self.live_cache.clear();
+                self.selection_cache.clear();
             }
 
             //// Building curve
+            Message::Move(point) if self.transform_drag.is_some() => {
+                let point = self.to_world(point);
+                self.drag_transform(point);
+            }
             #[allow(clippy::single_match)]
             Message::Move(point) => match &mut self.tool {
                 Tool::Pen(true) => {
-                    self.curves
-                        .last_mut()
-                        .unwrap()
-                        .push(point + Vector::new(self.camera.x, self.camera.y));
-                    self.cache.clear();
+                    let point = self.to_world(point);
+                    self.live_cache.clear();
+                    if let Some(point) = self.stabilizer.stabilize(point) {
+                        self.draw_to(point);
+                    }
+                }
+                Tool::Line(true) => {
+                    let end = self.to_world(point);
+                    if let (Some(start), Some(line)) = (self.drag_start, self.curves.last_mut()) {
+                        let end = match self.modifiers.shift() {
+                            true => snap_angle(start, end, SNAP_ANGLE),
+                            false => end,
+                        };
+                        *line = Curve::with_width(
+                            vec![
+                                Sample::new(start, DEFAULT_PRESSURE),
+                                Sample::new(end, DEFAULT_PRESSURE),
+                            ],
+                            self.active_color,
+                            self.stroke_width,
+                        )
+                        .on_layer(line.layer());
+                        self.live_cache.clear();
+                    }
+                }
+                Tool::Shape(kind, true) => {
+                    let kind = *kind;
+                    let end = self.to_world(point);
+                    if let (Some(start), Some(shape)) = (self.drag_start, self.curves.last_mut()) {
+                        shape.set_shape(Shape::from_drag(
+                            kind,
+                            start,
+                            end,
+                            self.modifiers.shift(),
+                            self.polygon_sides,
+                        ));
+                        self.live_cache.clear();
+                    }
+                }
+                Tool::Erase(true) => {
+                    let point = self.to_world(point);
+                    let from = self.erased_to.unwrap_or(point);
+                    self.erase(from, point);
+                    self.erased_to = Some(point);
+                }
+                Tool::Lasso(true) => {
+                    let point = self.to_world(point);
+                    let step = LASSO_STEP / self.zoom();
+                    if !self
+                        .lasso
+                        .last()
+                        .is_some_and(|last| last.distance(point) < step)
+                    {
+                        self.lasso.push(point);
+                    }
+                }
+                Tool::Marquee(true) => {
+                    if let Some(start) = self.drag_start {
+                        let end = self.to_world(point);
+                        self.lasso = vec![
+                            start,
+                            Point::new(end.x, start.y),
+                            end,
+                            Point::new(start.x, end.y),
+                        ];
+                    }
+                }
+                Tool::Region(true) => {
+                    let end = self.to_world(point);
+                    if let Some(start) = self.drag_start {
+                        self.export_region = Some(
+                            Rectangle::new(start, Size::ZERO)
+                                .union(&Rectangle::new(end, Size::ZERO)),
+                        );
+                    }
                 }
                 _ => {}
             },
 
             //// Finish curve
+            Message::MouseUp(_) if self.transform_drag.is_some() => self.finish_transform(),
             #[allow(clippy::single_match)]
             Message::MouseUp(_point) => match &mut self.tool {
                 Tool::Pen(true) => {
-                    if let Some(curve) = self.curves.last_mut() {
-                        *curve = curve.create_reduced(3)
+                    // the stroke ends where the pen was lifted, not where the stabilizer
+                    // had got to
+                    if let Some(point) = self.stabilizer.finish() {
+                        self.draw_to(point);
+                    }
+                    let tolerance = self.tolerance / self.zoom();
+                    // a click without moving leaves no stroke
+                    if let Some(curve) = self.curves.pop().filter(|curve| !curve.is_empty()) {
+                        let curve = match self.fit_curves {
+                            true => curve.fitted(tolerance.max(MIN_FIT_ERROR)),
+                            false => curve.simplified(tolerance),
+                        };
+                        self.edit(vec![Splice::insert(self.curves.len(), curve)]);
+                    }
+                    self.cache.clear();
+                    self.live_cache.clear();
+                    self.tool = Tool::Pen(false);
+                    self.pressure = None;
+                }
+                Tool::Line(true) => {
+                    // a click without dragging leaves no line
+                    if let Some(line) = self.curves.pop().filter(|line| line.len() >= 2) {
+                        let line = line.stamped(curve::now());
+                        self.edit(vec![Splice::insert(self.curves.len(), line)]);
+                    }
+                    self.cache.clear();
+                    self.live_cache.clear();
+                    self.tool = Tool::Line(false);
+                    self.drag_start = None;
+                }
+                Tool::Shape(kind, true) => {
+                    let kind = *kind;
+                    if let Some(shape) = self
+                        .curves
+                        .pop()
+                        .filter(|curve| curve.shape().is_some_and(|s| !s.is_degenerate()))
+                    {
+                        let shape = shape.stamped(curve::now());
+                        self.edit(vec![Splice::insert(self.curves.len(), shape)]);
                     }
                     self.cache.clear();
-                    self.tool = Tool::Pen(false)
+                    self.live_cache.clear();
+                    self.tool = Tool::Shape(kind, false);
+                    self.drag_start = None;
+                }
+                Tool::Erase(true) => {
+                    self.tool = Tool::Erase(false);
+                    self.erased_to = None;
+                    self.history.end();
+                }
+                Tool::Lasso(true) => {
+                    let lasso = std::mem::take(&mut self.lasso);
+                    // a click without drawing a loop picks nothing
+                    let picked = match lasso.len() >= 3 {
+                        true => self.enclosed(&lasso),
+                        false => vec![],
+                    };
+                    self.pick(picked);
+                    self.tool = Tool::Lasso(false);
+                }
+                Tool::Marquee(true) => {
+                    let corners = std::mem::take(&mut self.lasso);
+                    // a click without dragging picks nothing
+                    let picked = match corners[..] {
+                        [start, _, end, _] if start.x != end.x && start.y != end.y => {
+                            self.enclosed(&corners)
+                        }
+                        _ => vec![],
+                    };
+                    self.pick(picked);
+                    self.drag_start = None;
+                    self.tool = Tool::Marquee(false);
+                }
+                Tool::Region(true) => {
+                    // a click without dragging exports everything again
+                    self.export_region = self
+                        .export_region
+                        .filter(|region| region.width > 0. && region.height > 0.);
+                    self.tool = Tool::Region(false);
+                    self.drag_start = None;
                 }
-                Tool::Erase(true) => self.tool = Tool::Erase(false),
                 _ => {}
             },
+            // the canvas can't be drawn on while it's played back
+            Message::MouseDown(_) if self.playback.is_some() => {}
+            Message::MouseDown(_)
+                if matches!(
+                    self.tool,
+                    Tool::Pen(false) | Tool::Line(false) | Tool::Shape(_, false)
+                ) && !self.layers.active().is_editable() =>
+            {
+                self.status = Some(self.uneditable("drawn on"));
+            }
+            Message::MouseDown(point)
+                if self
+                    .transform_box()
+                    .and_then(|frame| self.grabbed(&frame, self.to_world(point)))
+                    .is_some() =>
+            {
+                self.start_transform(self.to_world(point));
+            }
             #[allow(clippy::single_match)]
-            Message::MouseDown(_point) => match &mut self.tool {
+            Message::MouseDown(point) => match &mut self.tool {
                 Tool::Pen(false) => {
                     self.tool = Tool::Pen(true);
-                    self.curves.push(Curve::new(vec![], self.active_color));
+                    self.stabilizer.reset();
+                    let layer = self.layers.active().id;
+                    self.curves.push(
+                        Curve::with_width(vec![], self.active_color, self.stroke_width)
+                            .on_layer(layer),
+                    );
+                    self.live_cache.clear();
                 }
 
-                Tool::Erase(false) => self.tool = Tool::Erase(true),
+                Tool::Line(false) => {
+                    self.tool = Tool::Line(true);
+                    self.drag_start = Some(self.to_world(point));
+                    let layer = self.layers.active().id;
+                    self.curves.push(
+                        Curve::with_width(vec![], self.active_color, self.stroke_width)
+                            .on_layer(layer),
+                    );
+                    self.live_cache.clear();
+                }
+                Tool::Shape(kind, false) => {
+                    let kind = *kind;
+                    let start = self.to_world(point);
+                    self.tool = Tool::Shape(kind, true);
+                    self.drag_start = Some(start);
+                    let shape = Shape::from_drag(kind, start, start, false, self.polygon_sides);
+                    let layer = self.layers.active().id;
+                    self.curves.push(
+                        Curve::from_shape(shape, self.active_color, self.stroke_width)
+                            .on_layer(layer),
+                    );
+                    self.live_cache.clear();
+                }
+                Tool::Erase(false) => {
+                    self.tool = Tool::Erase(true);
+                    // the whole drag is undone at once
+                    self.history.begin();
+                    let point = self.to_world(point);
+                    self.erase(point, point);
+                    self.erased_to = Some(point);
+                }
+                Tool::Lasso(false) => {
+                    self.tool = Tool::Lasso(true);
+                    self.lasso = vec![self.to_world(point)];
+                }
+                Tool::Marquee(false) => {
+                    self.tool = Tool::Marquee(true);
+                    self.drag_start = Some(self.to_world(point));
+                }
+                Tool::Region(false) => {
+                    self.tool = Tool::Region(true);
+                    let start = self.to_world(point);
+                    self.drag_start = Some(start);
+                    self.export_region = Some(Rectangle::new(start, Size::ZERO));
+                }
                 _ => (),
             },
             Message::SetTool(tool) => {
                 self.tool = tool.into();
             }
-            Message::SetColor(color) => self.active_color = color,
+            // with a selection, the style controls restyle it instead
+            // a swatch changes the color, not the opacity
+            Message::SetColor(color) if !self.selection.is_empty() => {
+                self.restyle(|curve| {
+                    curve.set_color(Color {
+                        a: curve.color().a,
+                        ..color
+                    })
+                });
+            }
+            Message::SetColor(color) => {
+                self.active_color = Color {
+                    a: self.active_color.a,
+                    ..color
+                }
+            }
+            Message::SetStrokeWidth(width) if !self.selection.is_empty() => {
+                self.restyle(|curve| curve.set_width(width));
+            }
+            Message::SetStrokeWidth(width) => self.stroke_width = width,
+            Message::SetStrokeOpacity(opacity) if !self.selection.is_empty() => {
+                self.restyle(|curve| {
+                    curve.set_color(Color {
+                        a: opacity,
+                        ..curve.color()
+                    })
+                });
+            }
+            Message::SetStrokeOpacity(opacity) => self.active_color.a = opacity,
+            Message::StartRestyle if !self.selection.is_empty() && !self.is_drawing() => {
+                self.restyle_drag = Some(RestyleDrag {
+                    preview: PreviewDrag::new(&self.selection, &self.curves),
+                    changed: false,
+                });
+            }
+            Message::StartRestyle => {}
+            Message::FinishRestyle => self.finish_restyle(),
+            // only a stroke being drawn takes pressure, it's forgotten when the stroke ends
+            Message::Pressure(pressure) if matches!(self.tool, Tool::Pen(true)) => {
+                self.pressure = Some(pressure)
+            }
+            Message::Pressure(_) => {}
+            Message::SetTolerance(tolerance) => self.tolerance = tolerance,
+            Message::SetFitCurves(fit_curves) => self.fit_curves = fit_curves,
+            Message::SetStabilizer(mode) => self.stabilizer.mode = mode,
+            Message::SetStabilizerStrength(strength) => self.stabilizer.strength = strength,
+            Message::SetEraserRadius(radius) => self.eraser_radius = radius,
+            Message::SetEraseMode(mode) => self.erase_mode = mode,
+            Message::SetPolygonSides(sides) => self.polygon_sides = sides,
+            Message::SetSelectMode(mode) => self.select_mode = mode,
+            Message::DeleteSelection if self.is_drawing() => {}
+            Message::DeleteSelection => {
+                let splices = self.splicing(|index, curve| {
+                    self.selection
+                        .binary_search(&index)
+                        .is_ok()
+                        .then(|| Splice::remove(index, curve.clone()))
+                });
+                self.edit(splices);
+            }
+            Message::Deselect => self.select(vec![]),
+            Message::Modifiers(modifiers) => self.modifiers = modifiers,
             Message::Clear => {
-                self.curves = vec![];
-                self.cache.clear();
+                // locked and hidden layers are left alone
+                self.remove_where(|layers, curve| layers.is_editable(curve));
+                self.live_cache.clear();
+            }
+            Message::Undo | Message::Redo | Message::JumpTo(_) if self.is_drawing() => {}
+            Message::Undo => {
+                if let Some(change) = self.history.undo() {
+                    self.revisit(change);
+                }
+            }
+            Message::Redo => {
+                if let Some(change) = self.history.redo() {
+                    self.revisit(change);
+                }
+            }
+            Message::JumpTo(node) => {
+                if let Some(change) = self.history.jump(node) {
+                    self.revisit(change);
+                }
+            }
+            Message::ShowHistory(show) => self.show_history = show,
+            Message::Play => match &mut self.playback {
+                Some(playback) => {
+                    if playback.is_finished() {
+                        playback.seek(0.);
+                    }
+                    playback.playing = true;
+                    self.cache.clear();
+                }
+                None => {
+                    let curves = self.layers.composed(&self.curves);
+                    self.playback = Some(Playback::new(&curves, self.playback_speed));
+                }
+            },
+            Message::Pause => {
+                if let Some(playback) = &mut self.playback {
+                    playback.playing = false;
+                }
+                self.last_tick = None;
+            }
+            Message::StopPlayback => self.stop_playback(),
+            Message::Seek(position) => self.play_back(|playback| playback.seek(position as f64)),
+            Message::SetPlaybackSpeed(speed) => {
+                self.playback_speed = speed;
+                if let Some(playback) = &mut self.playback {
+                    playback.speed = speed;
+                }
+            }
+            Message::AddLayer => {
+                self.layers.add();
+                self.layers_changed();
+            }
+            Message::DeleteLayer => {
+                let active = self.layers.active();
+                // a locked layer's curves can't be removed
+                if !active.locked {
+                    let id = active.id;
+                    let splices = self.removing(|_, curve| curve.layer() == id);
+                    if let Some(removal) = self.layers.remove(id) {
+                        // the layer comes back with its curves on undo
+                        self.apply(&splices);
+                        self.history.record_removal(splices, removal);
+                        self.layers_changed();
+                    }
+                }
+            }
+            Message::SelectLayer(id) => {
+                self.layers.set_active(id);
+                self.layers_changed();
+            }
+            Message::RenameLayer(name) => {
+                let id = self.layers.active().id;
+                if let Some(layer) = self.layers.get_mut(id) {
+                    layer.name = name;
+                }
+                self.renamed = true;
+            }
+            Message::FinishRename => {}
+            Message::ShowLayer(id, visible) => {
+                if let Some(layer) = self.layers.get_mut(id) {
+                    layer.visible = visible;
+                }
+                self.layers_changed();
+            }
+            Message::LockLayer(id, locked) => {
+                if let Some(layer) = self.layers.get_mut(id) {
+                    layer.locked = locked;
+                }
+                self.layers_changed();
+            }
+            Message::SetLayerOpacity(opacity) => {
+                let id = self.layers.active().id;
+                if let Some(layer) = self.layers.get_mut(id) {
+                    layer.opacity = opacity;
+                }
+                self.layers_changed();
+            }
+            Message::RaiseLayer => {
+                self.layers.raise(self.layers.active().id);
+                self.layers_changed();
+            }
+            Message::LowerLayer => {
+                self.layers.lower(self.layers.active().id);
+                self.layers_changed();
+            }
+            Message::Tick(now) => {
+                let elapsed = self.last_tick.map_or(0., |last| (now - last).as_secs_f64());
+                self.last_tick = Some(now);
+                self.play_back(|playback| playback.advance(elapsed));
+            }
+            Message::Recover => {
+                if let Some(document) = self.recovery.take() {
+                    self.load(&document);
+                    self.discard_recovered();
+                }
+            }
+            Message::DiscardRecovery => {
+                self.recovery = None;
+                self.discard_recovered();
+            }
+            Message::Open => {
+                if let Some(path) = file_dialog().pick_file() {
+                    match Document::load(&path) {
+                        Ok(document) => {
+                            self.load(&document);
+                            self.status = None;
+                            self.path = Some(path);
+                        }
+                        Err(error) => {
+                            self.status =
+                                Some(format!("Could not open {}: {error}", path.display()))
+                        }
+                    }
+                }
+            }
+            Message::Save => match self.path.clone() {
+                Some(path) => self.save(path),
+                None => return self.update(Message::SaveAs),
+            },
+            Message::SaveAs => {
+                if let Some(path) = file_dialog()
+                    .set_file_name(format!("untitled.{}", document::EXTENSION))
+                    .save_file()
+                {
+                    self.save(path);
+                }
+            }
+            Message::ExportSvg => match svg::export(&self.shown(), self.export_region) {
+                Some(svg) => self.write_export("SVG image", svg::EXTENSION, svg),
+                None => self.status = Some("Nothing to export".into()),
+            },
+            Message::ExportPng => {
+                match png::export(
+                    &self.shown(),
+                    self.export_region,
+                    self.png_scale,
+                    self.export_background,
+                ) {
+                    Ok(png) => self.write_export("PNG image", png::EXTENSION, png),
+                    Err(error) => self.status = Some(format!("Could not export: {error}")),
+                }
+            }
+            Message::ExportPdf => {
+                match pdf::export(
+                    &self.shown(),
+                    self.export_region,
+                    self.pdf_layout,
+                    self.export_background,
+                ) {
+                    Some(pdf) => self.write_export("PDF document", pdf::EXTENSION, pdf),
+                    None => self.status = Some("Nothing to export".into()),
+                }
+            }
+            Message::SetPngScale(scale) => self.png_scale = scale,
+            Message::SetPdfLayout(layout) => self.pdf_layout = layout,
+            Message::SetExportBackground(background) => self.export_background = background,
+            Message::ClearRegion => self.export_region = None,
+            Message::ImportSvg if !self.layers.active().is_editable() => {
+                self.status = Some(self.uneditable("imported onto"));
+            }
+            Message::ImportSvg => {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("SVG image", &[import::EXTENSION])
+                    .pick_file()
+                {
+                    // at the top left of the view
+                    match import::load(&path, self.to_world(Point::ORIGIN), self.active_color) {
+                        Ok(curves) => {
+                            let (time, layer) = (curve::now(), self.layers.active().id);
+                            self.edit(vec![Splice {
+                                index: self.curves.len(),
+                                removed: vec![],
+                                inserted: curves
+                                    .into_iter()
+                                    .map(|c| c.stamped(time).on_layer(layer))
+                                    .collect(),
+                            }]);
+                            self.status = Some(format!("Imported {}", path.display()));
+                        }
+                        Err(error) => {
+                            self.status =
+                                Some(format!("Could not import {}: {error}", path.display()))
+                        }
+                    }
+                }
+            }
+            // pasted curves would land between the stroke being drawn and its commit
+            Message::Paste(..) if self.is_drawing() => {}
+            Message::Paste(..) if !self.layers.active().is_editable() => {
+                self.status = Some(self.uneditable("pasted on"));
+            }
+            Message::Paste(text, point) => {
+                match clip::paste(&text, self.to_world(point), self.active_color) {
+                    Ok(curves) => {
+                        let (time, layer) = (curve::now(), self.layers.active().id);
+                        let start = self.curves.len();
+                        self.edit(vec![Splice {
+                            index: start,
+                            removed: vec![],
+                            inserted: curves
+                                .into_iter()
+                                .map(|c| c.stamped(time).on_layer(layer))
+                                .collect(),
+                        }]);
+                        // so what was pasted can be moved into place right away
+                        self.select((start..self.curves.len()).collect());
+                    }
+                    Err(error) => self.status = Some(format!("Could not paste: {error}")),
+                }
             }
             Message::DemoMessage => {}
         };
@@ -157,10 +1000,28 @@ impl Program for World {
     }
 
     fn view(&self) -> Element<'_, Message, iced::Theme, iced_wgpu::Renderer> {
+        //// The curve being drawn is kept out of the main cache
+        let (curves, live) = match (&self.playback, self.tool) {
+            (Some(playback), _) => (
+                playback.drawn().iter().map(|curve| (curve, 1.)).collect(),
+                playback.partial().map(|curve| (curve, 1.)),
+            ),
+            (None, Tool::Pen(true) | Tool::Line(true) | Tool::Shape(_, true)) => {
+                match self.curves.split_last() {
+                    Some((live, curves)) => (
+                        self.layers.compose(curves).collect(),
+                        Some((live, self.layers.get(live.layer()).opacity)),
+                    ),
+                    None => (vec![], None),
+                }
+            }
+            _ => (self.layers.compose(&self.curves).collect(), None),
+        };
+
         //let demo = self.curve_demo.view();
-        let workspace = workspace::workspace::<Message, iced::Theme, iced_wgpu::Renderer>(
+        let mut workspace = workspace::workspace::<Message, iced::Theme, iced_wgpu::Renderer>(
             &self.camera,
-            &self.curves,
+            curves,
             //vec![(demo.0, demo.1.map(|_| Message::DemoMessage))],
             vec![],
             &self.cache,
@@ -168,7 +1029,50 @@ impl Program for World {
         .pan(Message::Pan)
         .on_press(Message::MouseDown)
         .on_release(Message::MouseUp)
-        .on_move(Message::Move);
+        .on_move(Message::Move)
+        .on_modifiers(Message::Modifiers)
+        .on_key(shortcut)
+        .on_paste(Message::Paste);
+        if !self.selection.is_empty() {
+            workspace = workspace.on_copy(|| {
+                let selected: Vec<Curve> = self
+                    .selection
+                    .iter()
+                    .map(|&i| self.curves[i].clone())
+                    .collect();
+                clip::copy(&selected)
+            });
+            // cutting removes curves, which has to wait for a drag to end
+            if !self.is_drawing() {
+                workspace = workspace.on_cut(Message::DeleteSelection);
+            }
+        }
+        if let Some(region) = self.export_region {
+            workspace = workspace.region(region);
+        }
+        if !self.selection.is_empty() && self.playback.is_none() {
+            let selected = self.selection.iter().map(|&i| &self.curves[i]).collect();
+            workspace = workspace.selection(selected, &self.selection_cache);
+        }
+        if !self.lasso.is_empty() {
+            workspace = workspace.lasso(&self.lasso);
+        }
+        if let Some(drag) = &self.transform_drag {
+            let outline = drag
+                .frame
+                .corners()
+                .map(|corner| drag.transform.apply(corner));
+            workspace = workspace.transform_box(outline, vec![]);
+        } else if let Some(frame) = self.transform_box() {
+            let handles = frame.handles(ROTATE_STEM / self.zoom());
+            workspace = workspace.transform_box(frame.corners(), handles);
+        }
+        if let Some((live, opacity)) = live {
+            workspace = workspace.live(live, opacity, &self.live_cache);
+            if let Some((pen, cursor)) = self.stabilizer.guide().filter(|_| self.is_drawing()) {
+                workspace = workspace.guide(pen, cursor);
+            }
+        }
 
         let color_buttons = self.colors.iter().map(|c| {
             button("")
@@ -179,12 +1083,61 @@ impl Program for World {
                 .into()
         });
 
+        // the style of the selection, or of new strokes
+        let (width, opacity) = match self.selection.first() {
+            Some(&index) => (self.curves[index].width(), self.curves[index].color().a),
+            None => (self.stroke_width, self.active_color.a),
+        };
+        // dragging a slider restyles the selection as one step, other changes at once
+        let held = |slider| {
+            Held::new(slider)
+                .on_press(Message::StartRestyle)
+                .on_release(Message::FinishRestyle)
+        };
+        let style = row!(
+            text("Width"),
+            held(
+                slider(0.5..=20.0, width, Message::SetStrokeWidth)
+                    .step(0.5)
+                    .width(100.)
+            ),
+            text("Opacity"),
+            held(
+                slider(0.0..=1.0, opacity, Message::SetStrokeOpacity)
+                    .step(0.05)
+                    .width(100.)
+            ),
+        )
+        .spacing(10.)
+        .align_y(Alignment::Center);
+
         let pen = radio(
             "Pen",
             SelectedTool::Pen,
             Some(self.tool.into()),
             Message::SetTool,
         );
+        let line = radio(
+            "Line",
+            SelectedTool::Line,
+            Some(self.tool.into()),
+            Message::SetTool,
+        );
+        let shapes = row(ShapeKind::ALL.map(|kind| {
+            radio(
+                kind.to_string(),
+                SelectedTool::Shape(kind),
+                Some(self.tool.into()),
+                Message::SetTool,
+            )
+            .into()
+        }))
+        .spacing(10.);
+        let polygon_sides = row!(
+            text(format!("Sides: {}", self.polygon_sides)),
+            slider(3..=12, self.polygon_sides, Message::SetPolygonSides).width(100.)
+        )
+        .spacing(10.);
         let erase = radio(
             "Erase",
             SelectedTool::Erase,
@@ -192,10 +1145,148 @@ impl Program for World {
             Message::SetTool,
         );
 
+        let select = row!(
+            radio(
+                "Lasso",
+                SelectedTool::Lasso,
+                Some(self.tool.into()),
+                Message::SetTool,
+            ),
+            radio(
+                "Marquee",
+                SelectedTool::Marquee,
+                Some(self.tool.into()),
+                Message::SetTool,
+            ),
+            pick_list(
+                SelectMode::ALL,
+                Some(self.select_mode),
+                Message::SetSelectMode
+            ),
+            button("Delete")
+                .on_press_maybe((!self.selection.is_empty()).then_some(Message::DeleteSelection)),
+        )
+        .spacing(10.)
+        .align_y(Alignment::Center);
+
+        let export = row!(
+            radio(
+                "Region",
+                SelectedTool::Region,
+                Some(self.tool.into()),
+                Message::SetTool,
+            ),
+            button("Everything").on_press_maybe(self.export_region.map(|_| Message::ClearRegion)),
+            button("Export SVG").on_press(Message::ExportSvg),
+        )
+        .spacing(10.)
+        .align_y(Alignment::Center);
+
+        let export_png = row!(
+            button("Export PNG").on_press(Message::ExportPng),
+            text(format!("{}×", self.png_scale)),
+            slider(1.0..=8.0, self.png_scale, Message::SetPngScale)
+                .step(0.5)
+                .width(80.),
+        )
+        .spacing(10.)
+        .align_y(Alignment::Center);
+
+        let export_pdf = row!(
+            button("Export PDF").on_press(Message::ExportPdf),
+            pick_list(
+                pdf::Layout::ALL,
+                Some(self.pdf_layout),
+                Message::SetPdfLayout
+            ),
+        )
+        .spacing(10.)
+        .align_y(Alignment::Center);
+
+        let tolerance = row!(
+            text("Smoothing"),
+            slider(0.0..=4.0, self.tolerance, Message::SetTolerance)
+                .step(0.1)
+                .width(100.)
+        )
+        .spacing(10.);
+
+        let fit_curves = checkbox("Fit curves", self.fit_curves).on_toggle(Message::SetFitCurves);
+
+        let stabilizer = row!(
+            pick_list(
+                StabilizerMode::ALL,
+                Some(self.stabilizer.mode),
+                Message::SetStabilizer
+            ),
+            slider(
+                0.0..=1.0,
+                self.stabilizer.strength,
+                Message::SetStabilizerStrength
+            )
+            .step(0.05)
+            .width(100.)
+        )
+        .spacing(10.)
+        .align_y(Alignment::Center);
+
+        let eraser = row!(
+            pick_list(EraseMode::ALL, Some(self.erase_mode), Message::SetEraseMode),
+            slider(1.0..=40.0, self.eraser_radius, Message::SetEraserRadius).width(100.)
+        )
+        .spacing(10.)
+        .align_y(Alignment::Center);
+
+        let files = row!(
+            button("Open").on_press(Message::Open),
+            button("Save").on_press(Message::Save),
+            button("Save As").on_press(Message::SaveAs),
+            button("Import SVG").on_press(Message::ImportSvg),
+        )
+        .spacing(10.);
+
         let tools = column!(
-            row!(pen, erase).spacing(10.),
-            button("Clear").on_press(Message::Clear)
+            files,
+            export,
+            export_png,
+            export_pdf,
+            checkbox("Export background", self.export_background)
+                .on_toggle(Message::SetExportBackground),
+            row!(pen, line, erase).spacing(10.),
+            select,
+            shapes,
+            polygon_sides,
+            eraser,
+            tolerance,
+            fit_curves,
+            stabilizer,
+            row!(
+                button("Undo").on_press_maybe(self.history.can_undo().then_some(Message::Undo)),
+                button("Redo").on_press_maybe(self.history.can_redo().then_some(Message::Redo)),
+                button("Clear").on_press(Message::Clear),
+                checkbox("History", self.show_history).on_toggle(Message::ShowHistory),
+            )
+            .spacing(10.)
+            .align_y(Alignment::Center)
         )
+        .push(self.playback_controls())
+        .push_maybe(self.show_history.then(|| self.history_panel()))
+        .push_maybe(self.status.as_ref().map(text))
+        .push_maybe(self.recovery.as_ref().map(|document| {
+            column!(
+                text(format!(
+                    "monstera didn't shut down cleanly, restore {} strokes from the last session?",
+                    document.curves.len()
+                )),
+                row!(
+                    button("Restore").on_press(Message::Recover),
+                    button("Discard").on_press(Message::DiscardRecovery),
+                )
+                .spacing(10.)
+            )
+            .spacing(5.)
+            .width(300.)
+        }))
         .spacing(10.)
         .padding(10.);
 
@@ -203,13 +1294,15 @@ impl Program for World {
             workspace,
             column!(row!(
                 horizontal_space(),
+                self.layers_panel(),
                 column!(
                     row(color_buttons).spacing(5.0).padding(10.),
+                    container(style).padding([0, 10]),
                     container(tools).center_x(Length::Fill),
                 )
                 .width(Length::Shrink)
             )
-            .align_y(Alignment::Center),)
+            .align_y(Alignment::Start),)
             .padding(5.0),
             vertical_space()
         )
@@ -222,6 +1315,605 @@ impl World {
     pub fn background_color(&self) -> Color {
         Color::BLACK
     }
+
+    /// Screen pixels per world unit
+    pub fn zoom(&self) -> f32 {
+        DEFAULT_CAMERA_Z / self.camera.z
+    }
+
+    /// Everything that's saved with the document
+    pub fn document(&self) -> Document {
+        let canvas = self.canvas();
+        let history = self.history.data(&canvas.curves);
+        canvas.with_history(history)
+    }
+
+    /// The document without its history, as the journal keeps it
+    fn canvas(&self) -> Document {
+        Document::new(
+            self.camera.to_array(),
+            &self.colors,
+            self.active_color,
+            &self.curves,
+        )
+        .with_layers(&self.layers)
+    }
+
+    /// What the canvas shows, e.g. to export
+    fn shown(&self) -> Vec<Curve> {
+        self.layers.composed(&self.curves)
+    }
+
+    /// Replace the canvas with a document
+    pub fn load(&mut self, document: &Document) {
+        let camera = Vec3::from_array(document.camera);
+        // the zoom divides by the camera's height, so one that isn't above the canvas is left out
+        self.camera = if camera.is_finite() && camera.z > 0. {
+            camera
+        } else {
+            Vec3::new(0., 0., DEFAULT_CAMERA_Z)
+        };
+        self.colors = document.palette();
+        self.active_color = document.active_color();
+        self.curves = document.curves();
+        self.layers = document.layers();
+        self.tool = SelectedTool::from(self.tool).into();
+        // a history that doesn't lead to the curves is left behind
+        self.history = document
+            .history
+            .as_ref()
+            .and_then(|history| History::from_data(history, &self.curves))
+            .unwrap_or_default();
+        self.select(vec![]);
+        self.cache.clear();
+        self.live_cache.clear();
+        let document = self.canvas();
+        self.journal(|journal| journal.snapshot(&document));
+    }
+
+    /// Start logging edits for crash recovery, picking up what the last session left
+    /// behind if it didn't shut down cleanly
+    pub fn start_journal(&mut self) {
+        match Journal::start(&self.canvas()) {
+            Ok((journal, recovery)) => {
+                self.journal = Some(journal);
+                self.recovery = recovery;
+            }
+            Err(error) => self.status = Some(format!("Crash recovery is off: {error}")),
+        }
+    }
+
+    /// Mark the session as shut down cleanly
+    pub fn finish_journal(&self) {
+        if let Some(journal) = &self.journal {
+            // nothing left to warn about, the window is closing
+            let _ = journal.finish();
+        }
+    }
+
+    fn discard_recovered(&mut self) {
+        self.journal(|journal| journal.discard_recovered());
+    }
+
+    /// Write to the journal, giving up on crash recovery if that fails
+    fn journal(&mut self, write: impl FnOnce(&mut Journal) -> io::Result<()>) {
+        let Some(journal) = &mut self.journal else {
+            return;
+        };
+        let mut result = write(journal);
+        // a curve being drawn isn't committed, so it mustn't end up in the snapshot
+        if result.is_ok() && journal.is_long() && !self.is_drawing() {
+            let document = self.canvas();
+            if let Some(journal) = &mut self.journal {
+                result = journal.compact(&document);
+            }
+        }
+        if let Err(error) = result {
+            self.journal = None;
+            self.status = Some(format!("Crash recovery stopped: {error}"));
+        }
+    }
+
+    /// Apply `splices` in order as a step that can be undone, the only way committed
+    /// curves change
+    fn edit(&mut self, splices: Vec<Splice>) {
+        if splices.is_empty() {
+            return;
+        }
+        self.apply(&splices);
+        self.history.record(splices);
+    }
+
+    /// Apply `splices` in order, without recording them in the history
+    fn apply(&mut self, splices: &[Splice]) {
+        splices
+            .iter()
+            .for_each(|splice| splice.apply(&mut self.curves));
+        self.journal(|journal| splices.iter().try_for_each(|s| journal.splice(s)));
+        self.cache.clear();
+
+        // the selection follows its curves, those that were removed are dropped
+        let selection = std::mem::take(&mut self.selection)
+            .into_iter()
+            .filter_map(|index| {
+                splices
+                    .iter()
+                    .try_fold(index, |index, splice| splice.map_index(index))
+            })
+            .collect();
+        self.select(selection);
+    }
+
+    /// Select the curves at `indices`, in order
+    fn select(&mut self, indices: Vec<usize>) {
+        self.selection = indices;
+        self.selection_cache.clear();
+    }
+
+    /// Change the selection to the curves a lasso or marquee picked. With shift held
+    /// they're added to the selection, with alt they're taken out of it.
+    fn pick(&mut self, picked: Vec<usize>) {
+        let selection = if self.modifiers.shift() {
+            let mut selection = [&self.selection[..], &picked[..]].concat();
+            selection.sort_unstable();
+            selection.dedup();
+            selection
+        } else if self.modifiers.alt() {
+            let mut selection = self.selection.clone();
+            selection.retain(|index| !picked.contains(index));
+            selection
+        } else {
+            picked
+        };
+        self.select(selection);
+    }
+
+    /// Change the style of the selected curves as a single edit, or only show the
+    /// change while a slider is being dragged
+    fn restyle(&mut self, change: impl Fn(&mut Curve)) {
+        if let Some(drag) = &mut self.restyle_drag {
+            drag.preview.show(&mut self.curves, |curve| {
+                let mut restyled = curve.clone();
+                change(&mut restyled);
+                restyled
+            });
+            drag.changed = true;
+            self.cache.clear();
+            self.selection_cache.clear();
+            return;
+        }
+        let splices = self
+            .selection
+            .iter()
+            .map(|&index| {
+                let curve = self.curves[index].clone();
+                let mut restyled = curve.clone();
+                change(&mut restyled);
+                Splice::replace(index, curve, restyled)
+            })
+            .collect();
+        self.edit(splices);
+    }
+
+    /// Add a sample at `point` to the stroke being drawn
+    fn draw_to(&mut self, point: Point) {
+        let Some(curve) = self.curves.last_mut() else {
+            return;
+        };
+        let sample = match self.pressure {
+            Some(pressure) => Sample::new(point, pressure),
+            None => Sample::simulated(curve.last(), point),
+        };
+        curve.push(sample.at(curve::now()));
+    }
+
+    /// Why the active layer can't be `action`, e.g. "drawn on"
+    fn uneditable(&self, action: &str) -> String {
+        let layer = self.layers.active();
+        let state = match layer.visible {
+            true => "locked",
+            false => "hidden",
+        };
+        format!("{} is {state}, it can't be {action}", layer.name)
+    }
+
+    /// Box around the selection while a selection tool is picked
+    fn transform_box(&self) -> Option<TransformBox> {
+        if self.playback.is_some()
+            || !matches!(self.tool, Tool::Lasso(false) | Tool::Marquee(false))
+        {
+            return None;
+        }
+        self.selection
+            .iter()
+            .filter_map(|&index| self.curves[index].bounds())
+            .reduce(|a, b| a.union(&b))
+            .map(TransformBox::new)
+    }
+
+    /// The handle of `frame` at `point`, in world coordinates
+    fn grabbed(&self, frame: &TransformBox, point: Point) -> Option<Handle> {
+        let zoom = self.zoom();
+        frame.hit(point, HANDLE_RADIUS / zoom, ROTATE_STEM / zoom)
+    }
+
+    /// Start dragging the handle of the transform box at `point`
+    fn start_transform(&mut self, point: Point) {
+        let Some(frame) = self.transform_box() else {
+            return;
+        };
+        let Some(handle) = self.grabbed(&frame, point) else {
+            return;
+        };
+        self.transform_drag = Some(TransformDrag {
+            handle,
+            frame,
+            start: point,
+            preview: PreviewDrag::new(&self.selection, &self.curves),
+            transform: Transform::default(),
+        });
+    }
+
+    /// Show the selection transformed by dragging to `point`, without editing it yet
+    fn drag_transform(&mut self, point: Point) {
+        let Some(drag) = &mut self.transform_drag else {
+            return;
+        };
+        drag.transform = drag
+            .frame
+            .drag(drag.handle, drag.start, point, self.modifiers.shift());
+        let transform = drag.transform;
+        drag.preview
+            .show(&mut self.curves, |curve| curve.transformed(&transform));
+        self.cache.clear();
+        self.selection_cache.clear();
+    }
+
+    /// Edit the curves to where the drag left them, as a single step
+    fn finish_transform(&mut self) {
+        if let Some(drag) = self.transform_drag.take() {
+            self.finish_preview(drag.preview, !drag.transform.is_identity());
+        }
+    }
+
+    /// Edit the curves to the style the slider was let go at, as a single step
+    fn finish_restyle(&mut self) {
+        if let Some(drag) = self.restyle_drag.take() {
+            self.finish_preview(drag.preview, drag.changed);
+        }
+    }
+
+    /// Edit the curves to how `preview` shows them as a single step, if they `changed`
+    fn finish_preview(&mut self, preview: PreviewDrag, changed: bool) {
+        // put the curves back so the edit starts from what was saved
+        let splices = preview
+            .curves
+            .into_iter()
+            .map(|(index, curve)| {
+                let shown = std::mem::replace(&mut self.curves[index], curve.clone());
+                Splice::replace(index, curve, shown)
+            })
+            .collect();
+        self.cache.clear();
+        self.selection_cache.clear();
+        if changed {
+            self.edit(splices);
+        }
+    }
+
+    /// Indices of the editable curves inside `polygon`, or touching it, as the select
+    /// mode says
+    fn enclosed(&self, polygon: &[Point]) -> Vec<usize> {
+        self.curves
+            .iter()
+            .enumerate()
+            .filter(|(_, curve)| self.layers.is_editable(curve))
+            .filter(|(_, curve)| match self.select_mode {
+                SelectMode::Inside => curve.is_within_polygon(polygon),
+                SelectMode::Touching => curve.intersects_polygon(polygon),
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Remove the curves `remove` picks as a single edit
+    fn remove_where(&mut self, remove: impl Fn(&Layers, &Curve) -> bool) {
+        let splices = self.removing(remove);
+        self.edit(splices);
+    }
+
+    /// Splices removing the curves `remove` picks
+    fn removing(&self, remove: impl Fn(&Layers, &Curve) -> bool) -> Vec<Splice> {
+        self.splicing(|index, curve| {
+            remove(&self.layers, curve).then(|| Splice::remove(index, curve.clone()))
+        })
+    }
+
+    /// The splice `splice` makes of each curve at its index, if any, in an order they
+    /// can be applied in
+    fn splicing(&self, splice: impl Fn(usize, &Curve) -> Option<Splice>) -> Vec<Splice> {
+        // back to front, so earlier splices don't shift the indices of later ones
+        self.curves
+            .iter()
+            .enumerate()
+            .rev()
+            .filter_map(|(index, curve)| splice(index, curve))
+            .collect()
+    }
+
+    /// Go back or forth in the history, applying what it changes
+    fn revisit(&mut self, change: Change) {
+        self.apply(&change.splices);
+        if change.layers.is_empty() {
+            return;
+        }
+        for layer in &change.layers {
+            match layer {
+                LayerChange::Remove(id) => {
+                    self.layers.remove(*id);
+                }
+                LayerChange::Restore(removal) => self.layers.restore(removal),
+            }
+        }
+        self.layers_changed();
+    }
+
+    /// Redraw and journal after the layers changed
+    fn layers_changed(&mut self) {
+        self.cache.clear();
+        self.live_cache.clear();
+        // curves on hidden or locked layers can't be changed, so they can't be selected
+        let selection = std::mem::take(&mut self.selection)
+            .into_iter()
+            .filter(|&index| self.layers.is_editable(&self.curves[index]))
+            .collect();
+        self.select(selection);
+        self.journal_layers();
+    }
+
+    fn journal_layers(&mut self) {
+        self.renamed = false;
+        let layers = self.layers.clone();
+        self.journal(|journal| journal.layers(&layers));
+    }
+
+    /// Whether frames should be drawn continuously, rather than only on input
+    pub fn is_animating(&self) -> bool {
+        self.playback
+            .as_ref()
+            .is_some_and(|playback| playback.playing)
+    }
+
+    /// Change the playback, redrawing what it shows
+    fn play_back(&mut self, change: impl FnOnce(&mut Playback)) {
+        let Some(playback) = &mut self.playback else {
+            return;
+        };
+        let drawn = playback.drawn().len();
+        change(playback);
+        if playback.drawn().len() != drawn {
+            self.cache.clear();
+        }
+        if !playback.playing {
+            self.last_tick = None;
+        }
+        self.live_cache.clear();
+    }
+
+    /// Show the curves again instead of their playback
+    fn stop_playback(&mut self) {
+        self.playback = None;
+        self.last_tick = None;
+        self.cache.clear();
+        self.live_cache.clear();
+    }
+
+    /// Whether a drag is changing the curves, edits that would move them around under it
+    /// wait until it's done
+    fn is_drawing(&self) -> bool {
+        self.transform_drag.is_some()
+            || self.restyle_drag.is_some()
+            || matches!(
+                self.tool,
+                Tool::Pen(true) | Tool::Line(true) | Tool::Shape(_, true) | Tool::Erase(true)
+            )
+    }
+
+    fn save(&mut self, path: PathBuf) {
+        self.history.end();
+        match self.document().save(&path) {
+            Ok(()) => {
+                self.status = Some(format!("Saved {}", path.display()));
+                self.path = Some(path);
+            }
+            Err(error) => self.status = Some(format!("Could not save {}: {error}", path.display())),
+        }
+    }
+
+    /// Ask where to export `contents` as a `name` file and write it there
+    fn write_export(&mut self, name: &str, extension: &str, contents: impl AsRef<[u8]>) {
+        let Some(path) = export_dialog(name, extension) else {
+            return;
+        };
+        self.status = Some(match std::fs::write(&path, contents) {
+            Ok(()) => format!("Exported {}", path.display()),
+            Err(error) => format!("Could not export {}: {error}", path.display()),
+        });
+    }
+
+    /// Layers top first, with the settings of the active one below
+    fn layers_panel(&self) -> Element<'_, Message, iced::Theme, iced_wgpu::Renderer> {
+        let active = self.layers.active();
+        let layers = self.layers.iter().rev().map(|layer| {
+            let id = layer.id;
+            let style = match id == active.id {
+                true => button::primary,
+                false => button::secondary,
+            };
+            row!(
+                button(text(&layer.name))
+                    .style(style)
+                    .width(120.)
+                    .on_press(Message::SelectLayer(id)),
+                checkbox("Show", layer.visible).on_toggle(move |show| Message::ShowLayer(id, show)),
+                checkbox("Lock", layer.locked).on_toggle(move |lock| Message::LockLayer(id, lock)),
+            )
+            .spacing(10.)
+            .align_y(Alignment::Center)
+            .into()
+        });
+
+        column!(
+            text("Layers"),
+            column(layers).spacing(5.),
+            text_input("Name", &active.name)
+                .on_input(Message::RenameLayer)
+                .on_submit(Message::FinishRename)
+                .width(260.),
+            row!(
+                text("Opacity"),
+                slider(0.0..=1.0, active.opacity, Message::SetLayerOpacity)
+                    .step(0.05)
+                    .width(100.),
+            )
+            .spacing(10.)
+            .align_y(Alignment::Center),
+            row!(
+                button("Add").on_press(Message::AddLayer),
+                button("Delete").on_press_maybe(
+                    (self.layers.iter().count() > 1 && !active.locked)
+                        .then_some(Message::DeleteLayer)
+                ),
+                button("Up").on_press(Message::RaiseLayer),
+                button("Down").on_press(Message::LowerLayer),
+            )
+            .spacing(10.),
+        )
+        .spacing(10.)
+        .padding(10.)
+        .into()
+    }
+
+    /// Play button, or the controls of the playback going on
+    fn playback_controls(&self) -> Element<'_, Message, iced::Theme, iced_wgpu::Renderer> {
+        let speed = row!(
+            text(format!("{}×", self.playback_speed)),
+            slider(0.25..=8.0, self.playback_speed, Message::SetPlaybackSpeed)
+                .step(0.25)
+                .width(80.),
+        )
+        .spacing(10.)
+        .align_y(Alignment::Center);
+        let Some(playback) = &self.playback else {
+            return row!(button("Play back").on_press(Message::Play), speed)
+                .spacing(10.)
+                .align_y(Alignment::Center)
+                .into();
+        };
+
+        let play = match playback.playing {
+            true => button("Pause").on_press(Message::Pause),
+            false => button("Play").on_press(Message::Play),
+        };
+        let clock = |seconds: f64| format!("{}:{:02}", seconds as u32 / 60, seconds as u32 % 60);
+        column!(
+            row!(
+                play,
+                button("Stop").on_press(Message::StopPlayback),
+                text(format!(
+                    "{} / {}",
+                    clock(playback.position()),
+                    clock(playback.duration())
+                )),
+                speed,
+            )
+            .spacing(10.)
+            .align_y(Alignment::Center),
+            slider(
+                0.0..=playback.duration() as f32,
+                playback.position() as f32,
+                Message::Seek
+            )
+            .step(0.01)
+            .width(300.),
+        )
+        .spacing(5.)
+        .into()
+    }
+
+    /// The history tree, each branch indented below the one it splits off from
+    fn history_panel(&self) -> Element<'_, Message, iced::Theme, iced_wgpu::Renderer> {
+        let entries = self.history.entries().into_iter().map(|entry| {
+            let style = match entry.current {
+                true => button::primary,
+                false => button::text,
+            };
+            row!(
+                Space::with_width(12. * entry.depth as f32),
+                button(text(entry.label).size(14))
+                    .style(style)
+                    .padding([2, 6])
+                    .on_press(Message::JumpTo(entry.node)),
+            )
+            .into()
+        });
+        scrollable(column(entries).spacing(2.))
+            .height(200.)
+            .width(300.)
+            .into()
+    }
+
+    fn to_world(&self, point: Point) -> Point {
+        point + Vector::new(self.camera.x, self.camera.y)
+    }
+
+    /// Erase what the eraser touches moving from `from` to `to`
+    fn erase(&mut self, from: Point, to: Point) {
+        let radius = self.eraser_radius / self.zoom();
+        let splices = self.splicing(|index, curve| {
+            if !self.layers.is_editable(curve) {
+                return None;
+            }
+            match self.erase_mode {
+                EraseMode::Stroke => curve
+                    .touches(from, to, radius)
+                    .then(|| Splice::remove(index, curve.clone())),
+                EraseMode::Partial => curve.erased(from, to, radius).map(|pieces| Splice {
+                    index,
+                    removed: vec![curve.clone()],
+                    inserted: pieces,
+                }),
+            }
+        });
+        self.edit(splices);
+    }
+}
+
+impl Message {
+    /// Whether the message changes the curves, which ends playback
+    fn edits(&self) -> bool {
+        matches!(
+            self,
+            Message::Clear
+                | Message::Undo
+                | Message::Redo
+                | Message::JumpTo(_)
+                | Message::ImportSvg
+                | Message::Paste(..)
+                | Message::Open
+                | Message::Recover
+                | Message::DeleteLayer
+                | Message::DeleteSelection
+        )
+    }
+
+    /// Whether the message changes the style of the selected curves, if there are any
+    fn restyles(&self) -> bool {
+        matches!(
+            self,
+            Message::SetColor(_) | Message::SetStrokeWidth(_) | Message::SetStrokeOpacity(_)
+        )
+    }
 }
 
 mod style {